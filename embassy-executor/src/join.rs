@@ -0,0 +1,350 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::raw;
+use super::raw::util::UninitCell;
+
+/// Error returned by a [`JoinHandle`] when the task has no output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JoinError {
+    /// The task was aborted with [`JoinHandle::abort()`] before it completed.
+    Cancelled,
+}
+
+/// Handle to a spawned task, to wait for it to finish and get its output.
+///
+/// A `JoinHandle` is returned by [`Spawner::spawn()`](crate::Spawner::spawn) and
+/// [`SendSpawner::spawn()`](crate::SendSpawner::spawn). Awaiting it waits for the task to finish,
+/// and returns the value the task returned, or [`JoinError::Cancelled`] if the task was aborted.
+///
+/// The output of the task is kept in the task's storage until it is taken by the `JoinHandle`.
+/// Therefore, once the task has completed, the storage can't be used to spawn the task again until
+/// the `JoinHandle` is dropped.
+/// Dropping the `JoinHandle` does not stop the task, it just detaches it.
+///
+/// # Panics
+///
+/// Polling a `JoinHandle` again after it has returned the output panics.
+pub struct JoinHandle<T> {
+    task: raw::TaskRef,
+    output: *const UninitCell<T>,
+    /// Generation of the run of the task this handle belongs to.
+    generation: u32,
+    taken: bool,
+}
+
+// The output is produced by the executor thread, and can be taken from any thread.
+unsafe impl<T: Send> Send for JoinHandle<T> {}
+
+impl<T> JoinHandle<T> {
+    pub(crate) fn new(task: raw::TaskRef, output: *const UninitCell<T>) -> Self {
+        Self {
+            task,
+            output,
+            generation: task.generation(),
+            taken: false,
+        }
+    }
+
+    /// Check if the task has finished running, either because it completed or because it was aborted.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished(self.generation)
+    }
+
+    /// Abort the task.
+    ///
+    /// Instead of being polled again, the task's future is dropped the next time the executor
+    /// gets to it, and awaiting this `JoinHandle` returns [`JoinError::Cancelled`].
+    ///
+    /// Once the future is dropped, the task's slot in its pool is free, so the task can be spawned
+    /// again while this `JoinHandle` is still alive. The handle keeps referring to the aborted run.
+    ///
+    /// If the task is being polled, it is only dropped after that poll, so it may still complete
+    /// and return its output. If the task has already finished, this does nothing.
+    pub fn abort(&self) {
+        self.task.abort(self.generation)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.taken {
+            panic!("JoinHandle polled after the task output was taken")
+        }
+
+        match this.task.poll_join(this.generation, cx.waker()) {
+            Poll::Ready(result) => {
+                this.taken = true;
+                // safety: the task has completed, and the output is only taken here once.
+                Poll::Ready(result.map(|()| unsafe { (*this.output).read() }))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        let taken = self.taken;
+        let output = self.output;
+        unsafe {
+            self.task.release_join(self.generation, || {
+                if !taken {
+                    (*output).drop_in_place();
+                }
+            })
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::poll_fn;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::boxed::Box;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    use super::*;
+    use crate::raw::test_util::executor;
+    use crate::raw::{task_from_waker, TaskStorage};
+    use crate::SpawnError;
+
+    fn flag() -> &'static AtomicBool {
+        Box::leak(Box::new(AtomicBool::new(false)))
+    }
+
+    /// Yield to the executor until `flag` is set.
+    async fn wait_for(flag: &'static AtomicBool) {
+        poll_fn(|cx| {
+            if flag.load(Ordering::Relaxed) {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn count_waker() -> (Waker, Arc<CountWaker>) {
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        (count.clone().into(), count)
+    }
+
+    async fn pass<T>(value: T) -> T {
+        value
+    }
+
+    fn poll_join<T>(handle: &mut JoinHandle<T>, waker: &Waker) -> Poll<Result<T, JoinError>> {
+        Pin::new(handle).poll(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn join_returns_output() {
+        let (_lock, executor) = executor();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let done = flag();
+
+        let mut handle = executor
+            .spawner()
+            .spawn(storage.spawn(move || async move {
+                wait_for(done).await;
+                42
+            }))
+            .unwrap();
+        let (waker, count) = count_waker();
+
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Pending);
+        unsafe { executor.poll() };
+        assert!(!handle.is_finished());
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Pending);
+
+        // Finishing the task wakes the task awaiting the handle
+        done.store(true, Ordering::Relaxed);
+        unsafe { executor.poll() };
+        assert!(handle.is_finished());
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Ready(Ok(42)));
+    }
+
+    #[test]
+    fn handle_keeps_storage_busy() {
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let output = Arc::new(());
+
+        let handle = spawner.spawn(storage.spawn(|| pass(output.clone()))).unwrap();
+        unsafe { executor.poll() };
+        assert!(handle.is_finished());
+
+        // The output is kept for the live handle, so the storage can't be reused
+        assert_eq!(Arc::strong_count(&output), 2);
+        assert!(matches!(
+            spawner.spawn(storage.spawn(|| pass(output.clone()))),
+            Err(SpawnError::Busy)
+        ));
+
+        // Dropping the handle drops the output, and frees the storage
+        drop(handle);
+        assert_eq!(Arc::strong_count(&output), 1);
+        let mut handle = spawner.spawn(storage.spawn(|| pass(output.clone()))).unwrap();
+        unsafe { executor.poll() };
+        let (waker, _) = count_waker();
+        assert!(matches!(poll_join(&mut handle, &waker), Poll::Ready(Ok(_))));
+    }
+
+    #[test]
+    fn detached_task_frees_storage() {
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let done = flag();
+
+        // The handle is dropped while the task runs, so there's nobody to keep the output for
+        let handle = spawner.spawn(storage.spawn(move || wait_for(done))).unwrap();
+        unsafe { executor.poll() };
+        drop(handle);
+        assert!(matches!(
+            spawner.spawn(storage.spawn(move || wait_for(done))),
+            Err(SpawnError::Busy)
+        ));
+
+        done.store(true, Ordering::Relaxed);
+        unsafe { executor.poll() };
+        assert!(spawner.spawn(storage.spawn(move || wait_for(done))).is_ok());
+    }
+
+    /// Sets its flag when dropped.
+    struct DropFlag(&'static AtomicBool);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn abort_before_first_poll() {
+        let (_lock, executor) = executor();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let (polled, dropped) = (flag(), flag());
+
+        let drop_flag = DropFlag(dropped);
+        let mut handle = executor
+            .spawner()
+            .spawn(storage.spawn(move || async move {
+                let _drop_flag = drop_flag;
+                polled.store(true, Ordering::Relaxed);
+            }))
+            .unwrap();
+        handle.abort();
+        unsafe { executor.poll() };
+
+        assert!(!polled.load(Ordering::Relaxed));
+        assert!(dropped.load(Ordering::Relaxed));
+        let (waker, _) = count_waker();
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Ready(Err(JoinError::Cancelled)));
+    }
+
+    #[test]
+    fn abort_pending_task() {
+        let (_lock, executor) = executor();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let dropped = flag();
+
+        let drop_flag = DropFlag(dropped);
+        let mut handle = executor
+            .spawner()
+            .spawn(storage.spawn(move || async move {
+                let _drop_flag = drop_flag;
+                core::future::pending::<u32>().await
+            }))
+            .unwrap();
+        let (waker, count) = count_waker();
+        unsafe { executor.poll() };
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Pending);
+
+        // Aborting gets the task polled again, which drops its future
+        handle.abort();
+        assert!(!handle.is_finished());
+        unsafe { executor.poll() };
+        assert!(handle.is_finished());
+        assert!(dropped.load(Ordering::Relaxed));
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Ready(Err(JoinError::Cancelled)));
+
+        // Aborting a finished task does nothing
+        handle.abort();
+    }
+
+    #[test]
+    fn abort_during_poll_keeps_output() {
+        let (_lock, executor) = executor();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+
+        // The task is aborted while it is being polled, in the poll in which it completes
+        let mut handle = executor
+            .spawner()
+            .spawn(storage.spawn(|| async {
+                let task = poll_fn(|cx| Poll::Ready(task_from_waker(cx.waker()))).await;
+                task.abort(task.generation());
+                7
+            }))
+            .unwrap();
+        unsafe { executor.poll() };
+
+        let (waker, _) = count_waker();
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Ready(Ok(7)));
+    }
+
+    #[test]
+    fn respawn_after_abort() {
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let done = flag();
+
+        let mut aborted = spawner.spawn(storage.spawn(move || wait_for(done))).unwrap();
+        unsafe { executor.poll() };
+        aborted.abort();
+        unsafe { executor.poll() };
+        assert!(aborted.is_finished());
+
+        // The storage is free again, even though the handle is alive
+        let mut handle = spawner.spawn(storage.spawn(move || wait_for(done))).unwrap();
+        unsafe { executor.poll() };
+        assert!(!handle.is_finished());
+
+        // The old handle still refers to the aborted run, and doesn't affect the new one
+        let (waker, count) = count_waker();
+        assert!(aborted.is_finished());
+        aborted.abort();
+        assert_eq!(poll_join(&mut aborted, &waker), Poll::Ready(Err(JoinError::Cancelled)));
+        drop(aborted);
+
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Pending);
+        done.store(true, Ordering::Relaxed);
+        unsafe { executor.poll() };
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        assert_eq!(poll_join(&mut handle, &waker), Poll::Ready(Ok(())));
+        assert!(matches!(
+            spawner.spawn(storage.spawn(move || wait_for(done))),
+            Err(SpawnError::Busy)
+        ));
+    }
+}
@@ -73,6 +73,12 @@ pub mod export {
 
 pub mod raw;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+mod join;
+pub use join::*;
+
 mod spawner;
 pub use spawner::*;
 
@@ -0,0 +1,444 @@
+//! Runtime statistics of tasks.
+//!
+//! With the `metrics` feature, the executor keeps track of how much each task runs. The live tasks
+//! and their statistics can be listed with [`tasks()`], for example to periodically log which
+//! tasks are the busiest, like `top` does.
+//!
+//! A task that runs for a long time without awaiting blocks all the other tasks of its executor.
+//! Such polls can be detected by setting a budget with [`set_poll_budget()`]: polls taking longer
+//! are reported with a warning through `log` or `defmt`, or to the handler set with
+//! [`set_long_poll_handler()`].
+//!
+//! Poll times are measured with `embassy-time`, so a time driver is required.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+use embassy_time::Duration;
+
+use crate::raw::{TaskRef, STATE_SPAWNED};
+
+/// Why a task was last scheduled to be polled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeReason {
+    /// The task was just spawned.
+    Spawn,
+    /// The task was woken through its waker.
+    Waker,
+    /// A timer of the task expired.
+    Timer,
+}
+
+/// Statistics of a task since it was spawned.
+#[derive(Copy, Clone, Debug)]
+pub struct TaskStats {
+    /// How many times the task was polled.
+    pub poll_count: u32,
+    /// Total time spent polling the task.
+    pub poll_time: Duration,
+    /// Longest time a single poll of the task took.
+    pub max_poll_time: Duration,
+    /// Why the task was last scheduled to be polled.
+    pub last_wake: WakeReason,
+}
+
+impl TaskStats {
+    const fn new() -> Self {
+        Self {
+            poll_count: 0,
+            poll_time: Duration::from_ticks(0),
+            max_poll_time: Duration::from_ticks(0),
+            last_wake: WakeReason::Spawn,
+        }
+    }
+}
+
+/// A live task, as returned by [`tasks()`].
+#[derive(Copy, Clone, Debug)]
+pub struct TaskInfo {
+    /// Identifier of the task, unique among the live tasks. Matches the task ids used by `rtos-trace`.
+    pub id: u32,
+    /// Name of the task. Tasks declared with `#[embassy_executor::task]` are named after their function.
+    pub name: Option<&'static str>,
+    /// Statistics of the task.
+    pub stats: TaskStats,
+}
+
+/// A poll of a task that took longer than the budget set with [`set_poll_budget()`].
+#[derive(Copy, Clone, Debug)]
+pub struct LongPoll {
+    /// Identifier of the task, as in [`TaskInfo`].
+    pub id: u32,
+    /// Name of the task.
+    pub name: Option<&'static str>,
+    /// How long the poll took.
+    pub duration: Duration,
+    /// The budget the poll exceeded.
+    pub budget: Duration,
+}
+
+/// Function called for each [`LongPoll`].
+pub type LongPollHandler = fn(&LongPoll);
+
+static POLL_BUDGET: Mutex<Cell<Option<Duration>>> = Mutex::new(Cell::new(None));
+static LONG_POLL_HANDLER: Mutex<Cell<Option<LongPollHandler>>> = Mutex::new(Cell::new(None));
+
+/// Set the longest time a single poll of a task may take, for all executors.
+///
+/// Polls taking longer are reported, see [`set_long_poll_handler()`]. `None`, the default,
+/// disables the detection.
+pub fn set_poll_budget(budget: Option<Duration>) {
+    critical_section::with(|cs| POLL_BUDGET.borrow(cs).set(budget))
+}
+
+/// Set the function called when a poll of a task takes longer than the budget set with
+/// [`set_poll_budget()`].
+///
+/// The handler is called from the executor, right after the poll. With `None`, the default, a
+/// warning is logged instead.
+pub fn set_long_poll_handler(handler: Option<LongPollHandler>) {
+    critical_section::with(|cs| LONG_POLL_HANDLER.borrow(cs).set(handler))
+}
+
+/// Report the poll of `task` if it took longer than the budget.
+pub(crate) fn check_poll(task: TaskRef, duration: Duration) {
+    let (budget, handler, name) = critical_section::with(|cs| {
+        (
+            POLL_BUDGET.borrow(cs).get(),
+            LONG_POLL_HANDLER.borrow(cs).get(),
+            task.header().metrics.name.get(),
+        )
+    });
+
+    let budget = match budget {
+        Some(budget) if duration > budget => budget,
+        _ => return,
+    };
+
+    let long_poll = LongPoll {
+        id: task.as_ptr() as u32,
+        name,
+        duration,
+        budget,
+    };
+    match handler {
+        Some(handler) => handler(&long_poll),
+        None => warn!(
+            "task {} was polled for {} us, over the budget of {} us",
+            name.unwrap_or("<unnamed>"),
+            duration.as_micros(),
+            budget.as_micros()
+        ),
+    }
+}
+
+/// Metrics kept in the task header. Only accessed in a critical section.
+pub(crate) struct TaskMetrics {
+    name: Cell<Option<&'static str>>,
+    stats: Cell<TaskStats>,
+    /// Next task in the list of all tasks that were ever spawned.
+    next: Cell<Option<TaskRef>>,
+    registered: Cell<bool>,
+}
+
+impl TaskMetrics {
+    pub(crate) const fn new() -> Self {
+        Self {
+            name: Cell::new(None),
+            stats: Cell::new(TaskStats::new()),
+            next: Cell::new(None),
+            registered: Cell::new(false),
+        }
+    }
+
+    /// Reset the metrics when the task storage is spawned again.
+    pub(crate) fn reset(&self) {
+        critical_section::with(|_| {
+            self.name.set(None);
+            self.stats.set(TaskStats::new());
+        })
+    }
+
+    pub(crate) fn set_name(&self, name: &'static str) {
+        critical_section::with(|_| self.name.set(Some(name)))
+    }
+
+    pub(crate) fn record_wake(&self, reason: WakeReason) {
+        critical_section::with(|_| {
+            let mut stats = self.stats.get();
+            stats.last_wake = reason;
+            self.stats.set(stats);
+        })
+    }
+
+    pub(crate) fn record_poll(&self, duration: Duration) {
+        critical_section::with(|_| {
+            let mut stats = self.stats.get();
+            stats.poll_count = stats.poll_count.wrapping_add(1);
+            stats.poll_time += duration;
+            if duration > stats.max_poll_time {
+                stats.max_poll_time = duration;
+            }
+            self.stats.set(stats);
+        })
+    }
+}
+
+struct TaskList(Cell<Option<TaskRef>>);
+
+// Only accessed in a critical section.
+unsafe impl Sync for TaskList {}
+
+/// Head of the list of all tasks that were ever spawned.
+///
+/// Task storages are never deallocated, so tasks are never removed from the list. Instead,
+/// the tasks that are not spawned are skipped when iterating.
+static TASKS: TaskList = TaskList(Cell::new(None));
+
+/// Add a task that is being spawned to the list of tasks, if it's not in it yet.
+pub(crate) fn register(task: TaskRef) {
+    let metrics = &task.header().metrics;
+    metrics.record_wake(WakeReason::Spawn);
+    critical_section::with(|_| {
+        if !metrics.registered.get() {
+            metrics.registered.set(true);
+            metrics.next.set(TASKS.0.get());
+            TASKS.0.set(Some(task));
+        }
+    })
+}
+
+/// Iterate over the live tasks of all executors, with their statistics.
+///
+/// The statistics are a snapshot taken when the iterator gets to the task.
+pub fn tasks() -> Tasks {
+    Tasks {
+        next: critical_section::with(|_| TASKS.0.get()),
+    }
+}
+
+/// Iterator over the live tasks, returned by [`tasks()`].
+pub struct Tasks {
+    next: Option<TaskRef>,
+}
+
+impl Iterator for Tasks {
+    type Item = TaskInfo;
+
+    fn next(&mut self) -> Option<TaskInfo> {
+        critical_section::with(|_| {
+            while let Some(task) = self.next {
+                let header = task.header();
+                self.next = header.metrics.next.get();
+
+                if header.state.load(atomic_polyfill::Ordering::Acquire) & STATE_SPAWNED != 0 {
+                    return Some(TaskInfo {
+                        id: task.as_ptr() as u32,
+                        name: header.metrics.name.get(),
+                        stats: header.metrics.stats.get(),
+                    });
+                }
+            }
+            None
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::{pending, poll_fn};
+    use core::task::Poll;
+    use std::boxed::Box;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use embassy_time::Instant;
+
+    use super::*;
+    use crate::raw::test_util::executor;
+    use crate::raw::TaskStorage;
+
+    fn busy_wait(duration: Duration) {
+        let start = Instant::now();
+        while Instant::now() - start < duration {}
+    }
+
+    /// Let the executor poll the other tasks once.
+    async fn yield_now() {
+        let mut yielded = false;
+        poll_fn(|cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    async fn nop() {}
+
+    fn find(name: &str) -> Option<TaskInfo> {
+        let mut found = tasks().filter(|info| info.name == Some(name));
+        let info = found.next();
+        assert!(found.next().is_none(), "task {} listed twice", name);
+        info
+    }
+
+    #[test]
+    fn detects_long_polls() {
+        static LONG_POLLS: Mutex<Vec<LongPoll>> = Mutex::new(Vec::new());
+
+        fn handler(long_poll: &LongPoll) {
+            LONG_POLLS.lock().unwrap().push(*long_poll);
+        }
+
+        let (_lock, executor) = executor();
+        set_poll_budget(Some(Duration::from_millis(10)));
+        set_long_poll_handler(Some(handler));
+
+        let spawner = executor.spawner();
+
+        let quick = Box::leak(Box::new(TaskStorage::new()));
+        spawner.spawn(quick.spawn(|| async {}).with_name("quick")).unwrap();
+
+        let blocking = Box::leak(Box::new(TaskStorage::new()));
+        // Forget to await
+        let task = blocking.spawn(|| async { busy_wait(Duration::from_millis(20)) });
+        spawner.spawn(task.with_name("blocking")).unwrap();
+
+        unsafe { executor.poll() };
+
+        set_poll_budget(None);
+        set_long_poll_handler(None);
+
+        let long_polls = LONG_POLLS.lock().unwrap();
+        assert_eq!(long_polls.len(), 1);
+        assert_eq!(long_polls[0].name, Some("blocking"));
+        assert!(long_polls[0].duration >= Duration::from_millis(20));
+        assert_eq!(long_polls[0].budget, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn counts_polls() {
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let task = storage.spawn(|| async {
+            busy_wait(Duration::from_millis(2));
+            yield_now().await;
+            busy_wait(Duration::from_millis(4));
+            pending::<()>().await
+        });
+        spawner.spawn(task.with_name("counted")).unwrap();
+
+        let stats = find("counted").unwrap().stats;
+        assert_eq!(stats.poll_count, 0);
+        assert_eq!(stats.poll_time, Duration::from_ticks(0));
+        assert_eq!(stats.last_wake, WakeReason::Spawn);
+
+        unsafe { executor.poll() };
+        let stats = find("counted").unwrap().stats;
+        assert_eq!(stats.poll_count, 1);
+        assert!(stats.poll_time >= Duration::from_millis(2));
+        assert_eq!(stats.max_poll_time, stats.poll_time);
+        assert_eq!(stats.last_wake, WakeReason::Waker);
+
+        unsafe { executor.poll() };
+        let stats = find("counted").unwrap().stats;
+        assert_eq!(stats.poll_count, 2);
+        assert!(stats.poll_time >= Duration::from_millis(6));
+        assert!(stats.max_poll_time >= Duration::from_millis(4));
+        assert!(stats.max_poll_time < stats.poll_time);
+    }
+
+    #[test]
+    fn lists_live_tasks() {
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+
+        let done = Box::leak(Box::new(TaskStorage::new()));
+        spawner.spawn(done.spawn(nop).with_name("done")).unwrap();
+        let live = Box::leak(Box::new(TaskStorage::new()));
+        spawner.spawn(live.spawn(pending::<()>).with_name("live")).unwrap();
+
+        let done_id = find("done").unwrap().id;
+        let live_id = find("live").unwrap().id;
+        assert_ne!(done_id, live_id);
+
+        unsafe { executor.poll() };
+        assert!(find("done").is_none());
+        assert_eq!(find("live").unwrap().id, live_id);
+
+        // Spawning the storage again lists it again, with fresh statistics and name.
+        spawner.spawn(done.spawn(nop)).unwrap();
+        let respawned = tasks().find(|info| info.id == done_id).unwrap();
+        assert_eq!(respawned.name, None);
+        assert_eq!(respawned.stats.poll_count, 0);
+
+        unsafe { executor.poll() };
+        assert!(tasks().all(|info| info.id != done_id));
+    }
+
+    #[cfg(feature = "integrated-timers")]
+    #[test]
+    fn records_timer_wakes() {
+        use embassy_time::Timer;
+
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let task = storage.spawn(|| async {
+            Timer::after(Duration::from_millis(1)).await;
+            pending::<()>().await
+        });
+        spawner.spawn(task.with_name("sleeper")).unwrap();
+
+        unsafe { executor.poll() };
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        unsafe { executor.poll() };
+
+        let stats = find("sleeper").unwrap().stats;
+        assert_eq!(stats.poll_count, 2);
+        assert_eq!(stats.last_wake, WakeReason::Timer);
+    }
+
+    #[cfg(feature = "integrated-timers")]
+    #[test]
+    fn expired_timer_of_queued_task_is_not_the_wake_reason() {
+        use core::future::Future;
+        use core::pin::Pin;
+
+        use embassy_time::Timer;
+
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+
+        let storage = Box::leak(Box::new(TaskStorage::new()));
+        let task = storage.spawn(|| async {
+            // Woken by its waker, and by a timer expiring before the next poll.
+            let mut timer = Timer::after(Duration::from_millis(1));
+            poll_fn(|cx| {
+                let _ = Pin::new(&mut timer).poll(cx);
+                Poll::Ready(())
+            })
+            .await;
+            yield_now().await;
+            pending::<()>().await
+        });
+        spawner.spawn(task.with_name("timer")).unwrap();
+
+        unsafe { executor.poll() };
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        unsafe { executor.poll() };
+
+        let stats = find("timer").unwrap().stats;
+        assert_eq!(stats.poll_count, 2);
+        assert_eq!(stats.last_wake, WakeReason::Waker);
+    }
+}
@@ -8,6 +8,8 @@
 //! [executor wrappers](crate::Executor) and the [`embassy_executor::task`](embassy_macros::task) macro, which are fully safe.
 
 mod run_queue;
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod test_util;
 #[cfg(feature = "integrated-timers")]
 mod timer_queue;
 pub(crate) mod util;
@@ -18,13 +20,13 @@ use core::future::Future;
 use core::mem;
 use core::pin::Pin;
 use core::ptr::NonNull;
-use core::task::{Context, Poll};
+use core::task::{Context, Poll, Waker};
 
 use atomic_polyfill::{AtomicU32, Ordering};
 use critical_section::CriticalSection;
 #[cfg(feature = "integrated-timers")]
 use embassy_time::driver::{self, AlarmHandle};
-#[cfg(feature = "integrated-timers")]
+#[cfg(any(feature = "integrated-timers", feature = "metrics"))]
 use embassy_time::Instant;
 #[cfg(feature = "rtos-trace")]
 use rtos_trace::trace;
@@ -32,7 +34,7 @@ use rtos_trace::trace;
 use self::run_queue::{RunQueue, RunQueueItem};
 use self::util::UninitCell;
 pub use self::waker::task_from_waker;
-use super::SpawnToken;
+use super::{JoinError, SpawnToken};
 
 /// Task is spawned (has a future)
 pub(crate) const STATE_SPAWNED: u32 = 1 << 0;
@@ -41,18 +43,36 @@ pub(crate) const STATE_RUN_QUEUED: u32 = 1 << 1;
 /// Task is in the executor timer queue
 #[cfg(feature = "integrated-timers")]
 pub(crate) const STATE_TIMER_QUEUED: u32 = 1 << 2;
+/// A [`JoinHandle`](crate::JoinHandle) for the task exists
+pub(crate) const STATE_JOIN_HANDLE: u32 = 1 << 3;
+/// Task is aborted, its future is dropped instead of polled
+pub(crate) const STATE_ABORTED: u32 = 1 << 4;
+
+/// Highest priority a task can have. Tasks have priority 0 (the lowest) by default.
+///
+/// See [`SpawnToken::with_priority()`](crate::SpawnToken::with_priority).
+pub const MAX_PRIORITY: u8 = 3;
 
 /// Raw task header for use in task pointers.
 pub(crate) struct TaskHeader {
     pub(crate) state: AtomicU32,
     pub(crate) run_queue_item: RunQueueItem,
+    pub(crate) priority: Cell<u8>,
     pub(crate) executor: Cell<Option<&'static Executor>>,
     poll_fn: Cell<Option<unsafe fn(TaskRef)>>,
+    /// Waker of the task awaiting the `JoinHandle`. Only accessed in a critical section.
+    join_waker: Cell<Option<Waker>>,
+    /// Incremented when an aborted task frees its storage while its `JoinHandle` is alive, so the
+    /// handle can tell that its run is over. Only accessed in a critical section.
+    generation: Cell<u32>,
 
     #[cfg(feature = "integrated-timers")]
     pub(crate) expires_at: Cell<Instant>,
     #[cfg(feature = "integrated-timers")]
     pub(crate) timer_queue_item: timer_queue::TimerQueueItem,
+
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: crate::metrics::TaskMetrics,
 }
 
 /// This is essentially a `&'static TaskStorage<F>` where the type of the future has been erased.
@@ -83,16 +103,119 @@ impl TaskRef {
     pub(crate) fn as_ptr(self) -> *const TaskHeader {
         self.ptr.as_ptr()
     }
+
+    /// The generation of the current run of the task, see [`TaskHeader::generation`].
+    pub(crate) fn generation(self) -> u32 {
+        critical_section::with(|_| self.header().generation.get())
+    }
+
+    /// Check if the run of the task with the given generation was aborted. Its storage is then
+    /// free, and may already hold another run.
+    ///
+    /// Must be called in a critical section.
+    fn is_cancelled(self, generation: u32) -> bool {
+        self.header().generation.get() != generation
+    }
+
+    /// Check if the run of the task with the given generation has finished running.
+    pub(crate) fn is_finished(self, generation: u32) -> bool {
+        critical_section::with(|_| {
+            self.is_cancelled(generation) || self.header().state.load(Ordering::Acquire) & STATE_SPAWNED == 0
+        })
+    }
+
+    /// Check if the run of the task with the given generation has finished running. If not,
+    /// `waker` is woken when it does.
+    ///
+    /// Returns `Ok` if the task completed, and its output can be taken.
+    ///
+    /// Only the `JoinHandle` of the task may call this.
+    pub(crate) fn poll_join(self, generation: u32, waker: &Waker) -> Poll<Result<(), JoinError>> {
+        critical_section::with(|_| {
+            let header = self.header();
+            if self.is_cancelled(generation) {
+                return Poll::Ready(Err(JoinError::Cancelled));
+            }
+            if header.state.load(Ordering::Acquire) & STATE_SPAWNED == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            let waker = match header.join_waker.take() {
+                Some(w) if w.will_wake(waker) => w,
+                _ => waker.clone(),
+            };
+            header.join_waker.set(Some(waker));
+            Poll::Pending
+        })
+    }
+
+    /// Abort the run of the task with the given generation. Its future is dropped the next time
+    /// it would be polled.
+    ///
+    /// This does nothing if that run has already finished.
+    pub(crate) fn abort(self, generation: u32) {
+        let spawned = critical_section::with(|_| {
+            let header = self.header();
+            if self.is_cancelled(generation) || header.state.load(Ordering::Acquire) & STATE_SPAWNED == 0 {
+                return false;
+            }
+            header.state.fetch_or(STATE_ABORTED, Ordering::AcqRel);
+            true
+        });
+
+        // Get the task polled, so it notices it has been aborted.
+        if spawned {
+            wake_task(self);
+        }
+    }
+
+    /// Give up the `JoinHandle` of the run of the task with the given generation, so the task
+    /// storage can be reused once the task has finished.
+    ///
+    /// If the task has already completed, `drop_output` is called to drop its output first.
+    ///
+    /// # Safety
+    ///
+    /// Only the `JoinHandle` of the task may call this, once.
+    pub(crate) unsafe fn release_join(self, generation: u32, drop_output: impl FnOnce()) {
+        let header = self.header();
+        let completed = critical_section::with(|_| {
+            if self.is_cancelled(generation) {
+                // The storage was freed when the task was aborted.
+                return false;
+            }
+            if header.state.load(Ordering::Acquire) & STATE_SPAWNED == 0 {
+                return true;
+            }
+            header.state.fetch_and(!STATE_JOIN_HANDLE, Ordering::AcqRel);
+            header.join_waker.set(None);
+            false
+        });
+
+        if completed {
+            // The storage can't be claimed again while STATE_JOIN_HANDLE is set, so the
+            // output is still there.
+            drop_output();
+            header.state.fetch_and(!STATE_JOIN_HANDLE, Ordering::AcqRel);
+        }
+    }
 }
 
 /// Raw storage in which a task can be spawned.
 ///
-/// This struct holds the necessary memory to spawn one task whose future is `F`.
+/// This struct holds the necessary memory to spawn one task whose future is `F`, and to keep
+/// its output until it is taken by the task's [`JoinHandle`](crate::JoinHandle).
 /// At a given time, the `TaskStorage` may be in spawned or not-spawned state. You
 /// may spawn it with [`TaskStorage::spawn()`], which will fail if it is already spawned.
 ///
+/// Besides the future, the storage of every task holds the waker of the task awaiting its
+/// `JoinHandle`, which takes two words, and a slot for the output of the task, which takes no space
+/// for tasks returning `()`.
+///
 /// A `TaskStorage` must live forever, it may not be deallocated even after the task has finished
-/// running. Hence the relevant methods require `&'static self`. It may be reused, however.
+/// running. Hence the relevant methods require `&'static self`. It may be reused, however, once the
+/// task has finished running and its `JoinHandle` has been dropped, or once the task has been
+/// aborted.
 ///
 /// Internally, the [embassy_executor::task](embassy_macros::task) macro allocates an array of `TaskStorage`s
 /// in a `static`. The most common reason to use the raw `Task` is to have control of where
@@ -103,7 +226,8 @@ impl TaskRef {
 #[repr(C)]
 pub struct TaskStorage<F: Future + 'static> {
     raw: TaskHeader,
-    future: UninitCell<F>, // Valid if STATE_SPAWNED
+    future: UninitCell<F>,         // Valid if STATE_SPAWNED
+    output: UninitCell<F::Output>, // Valid if STATE_JOIN_HANDLE and not STATE_SPAWNED, until taken
 }
 
 impl<F: Future + 'static> TaskStorage<F> {
@@ -115,16 +239,23 @@ impl<F: Future + 'static> TaskStorage<F> {
             raw: TaskHeader {
                 state: AtomicU32::new(0),
                 run_queue_item: RunQueueItem::new(),
+                priority: Cell::new(0),
                 executor: Cell::new(None),
                 // Note: this is lazily initialized so that a static `TaskStorage` will go in `.bss`
                 poll_fn: Cell::new(None),
+                join_waker: Cell::new(None),
+                generation: Cell::new(0),
 
                 #[cfg(feature = "integrated-timers")]
                 expires_at: Cell::new(Instant::from_ticks(0)),
                 #[cfg(feature = "integrated-timers")]
                 timer_queue_item: timer_queue::TimerQueueItem::new(),
+
+                #[cfg(feature = "metrics")]
+                metrics: crate::metrics::TaskMetrics::new(),
             },
             future: UninitCell::uninit(),
+            output: UninitCell::uninit(),
         }
     }
 
@@ -135,18 +266,20 @@ impl<F: Future + 'static> TaskStorage<F> {
     /// the future is constructed in-place, avoiding a temporary copy in the stack thanks to
     /// NRVO optimizations.
     ///
-    /// This function will fail if the task is already spawned and has not finished running.
+    /// This function will fail if the task is already spawned and has not finished running, or
+    /// if the previous run completed and its [`JoinHandle`](crate::JoinHandle) has not been
+    /// dropped yet.
     /// In this case, the error is delayed: a "poisoned" SpawnToken is returned, which will
     /// cause [`Spawner::spawn()`](super::Spawner::spawn) to return the error.
     ///
     /// Once the task has finished running, you may spawn it again. It is allowed to spawn it
     /// on a different executor.
-    pub fn spawn(&'static self, future: impl FnOnce() -> F) -> SpawnToken<impl Sized> {
+    pub fn spawn(&'static self, future: impl FnOnce() -> F) -> SpawnToken<impl Sized, F::Output> {
         let task = AvailableTask::claim(self);
         match task {
             Some(task) => {
-                let task = task.initialize(future);
-                unsafe { SpawnToken::<F>::new(task) }
+                let (task, output) = task.initialize(future);
+                unsafe { SpawnToken::<F, _>::new(task, output) }
             }
             None => SpawnToken::new_failed(),
         }
@@ -155,13 +288,19 @@ impl<F: Future + 'static> TaskStorage<F> {
     unsafe fn poll(p: TaskRef) {
         let this = &*(p.as_ptr() as *const TaskStorage<F>);
 
+        if this.raw.state.load(Ordering::Acquire) & STATE_ABORTED != 0 {
+            this.future.drop_in_place();
+            this.finish(None);
+            return;
+        }
+
         let future = Pin::new_unchecked(this.future.as_mut());
         let waker = waker::from_task(p);
         let mut cx = Context::from_waker(&waker);
         match future.poll(&mut cx) {
-            Poll::Ready(_) => {
+            Poll::Ready(output) => {
                 this.future.drop_in_place();
-                this.raw.state.fetch_and(!STATE_SPAWNED, Ordering::AcqRel);
+                this.finish(Some(output));
             }
             Poll::Pending => {}
         }
@@ -170,6 +309,46 @@ impl<F: Future + 'static> TaskStorage<F> {
         // it's a noop for our waker.
         mem::forget(waker);
     }
+
+    /// Mark the task as not spawned after its future has been dropped, and wake the task awaiting
+    /// its `JoinHandle`.
+    ///
+    /// `output` is `None` if the task was aborted.
+    unsafe fn finish(&self, output: Option<F::Output>) {
+        let (output, joiner) = critical_section::with(|_| {
+            // The output is only kept if there's a JoinHandle to take it.
+            if self.raw.state.load(Ordering::Acquire) & STATE_JOIN_HANDLE == 0 {
+                self.raw
+                    .state
+                    .fetch_and(!(STATE_SPAWNED | STATE_ABORTED), Ordering::AcqRel);
+                return (output, None);
+            }
+
+            match output {
+                Some(output) => {
+                    // The task completed, even if it was aborted in the meantime.
+                    self.output.write(output);
+                    self.raw
+                        .state
+                        .fetch_and(!(STATE_SPAWNED | STATE_ABORTED), Ordering::AcqRel);
+                }
+                // Without an output, there's nothing to keep the storage for. The JoinHandle
+                // sees the new generation, and knows the task was cancelled.
+                None => {
+                    self.raw.generation.set(self.raw.generation.get().wrapping_add(1));
+                    self.raw
+                        .state
+                        .fetch_and(!(STATE_SPAWNED | STATE_ABORTED | STATE_JOIN_HANDLE), Ordering::AcqRel);
+                }
+            }
+            (None, self.raw.join_waker.take())
+        });
+
+        drop(output);
+        if let Some(joiner) = joiner {
+            joiner.wake();
+        }
+    }
 }
 
 unsafe impl<F: Future + 'static> Sync for TaskStorage<F> {}
@@ -182,17 +361,25 @@ impl<F: Future + 'static> AvailableTask<F> {
     fn claim(task: &'static TaskStorage<F>) -> Option<Self> {
         task.raw
             .state
-            .compare_exchange(0, STATE_SPAWNED | STATE_RUN_QUEUED, Ordering::AcqRel, Ordering::Acquire)
+            .compare_exchange(
+                0,
+                STATE_SPAWNED | STATE_RUN_QUEUED | STATE_JOIN_HANDLE,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
             .ok()
             .map(|_| Self { task })
     }
 
-    fn initialize(self, future: impl FnOnce() -> F) -> TaskRef {
+    fn initialize(self, future: impl FnOnce() -> F) -> (TaskRef, *const UninitCell<F::Output>) {
         unsafe {
             self.task.raw.poll_fn.set(Some(TaskStorage::<F>::poll));
+            self.task.raw.priority.set(0);
+            #[cfg(feature = "metrics")]
+            self.task.raw.metrics.reset();
             self.task.future.write(future());
         }
-        TaskRef::new(self.task)
+        (TaskRef::new(self.task), &self.task.output)
     }
 }
 
@@ -218,12 +405,12 @@ impl<F: Future + 'static, const N: usize> TaskPool<F, N> {
     /// This will loop over the pool and spawn the task in the first storage that
     /// is currently free. If none is free, a "poisoned" SpawnToken is returned,
     /// which will cause [`Spawner::spawn()`](super::Spawner::spawn) to return the error.
-    pub fn spawn(&'static self, future: impl FnOnce() -> F) -> SpawnToken<impl Sized> {
+    pub fn spawn(&'static self, future: impl FnOnce() -> F) -> SpawnToken<impl Sized, F::Output> {
         let task = self.pool.iter().find_map(AvailableTask::claim);
         match task {
             Some(task) => {
-                let task = task.initialize(future);
-                unsafe { SpawnToken::<F>::new(task) }
+                let (task, output) = task.initialize(future);
+                unsafe { SpawnToken::<F, _>::new(task, output) }
             }
             None => SpawnToken::new_failed(),
         }
@@ -238,7 +425,7 @@ impl<F: Future + 'static, const N: usize> TaskPool<F, N> {
     /// SAFETY: `future` must be a closure of the form `move || my_async_fn(args)`, where `my_async_fn`
     /// is an `async fn`, NOT a hand-written `Future`.
     #[doc(hidden)]
-    pub unsafe fn _spawn_async_fn<FutFn>(&'static self, future: FutFn) -> SpawnToken<impl Sized>
+    pub unsafe fn _spawn_async_fn<FutFn>(&'static self, future: FutFn) -> SpawnToken<impl Sized, F::Output>
     where
         FutFn: FnOnce() -> F,
     {
@@ -271,8 +458,8 @@ impl<F: Future + 'static, const N: usize> TaskPool<F, N> {
         let task = self.pool.iter().find_map(AvailableTask::claim);
         match task {
             Some(task) => {
-                let task = task.initialize(future);
-                unsafe { SpawnToken::<FutFn>::new(task) }
+                let (task, output) = task.initialize(future);
+                unsafe { SpawnToken::<FutFn, _>::new(task, output) }
             }
             None => SpawnToken::new_failed(),
         }
@@ -288,7 +475,7 @@ impl<F: Future + 'static, const N: usize> TaskPool<F, N> {
 /// The raw executor leaves it up to you to handle wakeups and scheduling:
 ///
 /// - To get the executor to do work, call `poll()`. This will poll all queued tasks (all tasks
-///   that "want to run").
+///   that "want to run"), the ones with a higher priority first.
 /// - You must supply a `signal_fn`. The executor will call it to notify you it has work
 ///   to do. You must arrange for `poll()` to be called as soon as possible.
 ///
@@ -365,6 +552,9 @@ impl Executor {
         #[cfg(feature = "rtos-trace")]
         trace::task_new(task.as_ptr() as u32);
 
+        #[cfg(feature = "metrics")]
+        crate::metrics::register(task);
+
         critical_section::with(|cs| {
             self.enqueue(cs, task);
         })
@@ -374,6 +564,7 @@ impl Executor {
     ///
     /// This loops over all tasks that are queued to be polled (i.e. they're
     /// freshly spawned or they've been woken). Other tasks are not polled.
+    /// Tasks with a higher priority are polled first.
     ///
     /// You must call `poll` after receiving a call to `signal_fn`. It is OK
     /// to call `poll` even when not requested by `signal_fn`, but it wastes
@@ -390,7 +581,14 @@ impl Executor {
     pub unsafe fn poll(&'static self) {
         loop {
             #[cfg(feature = "integrated-timers")]
-            self.timer_queue.dequeue_expired(Instant::now(), |task| wake_task(task));
+            self.timer_queue.dequeue_expired(Instant::now(), |task| {
+                critical_section::with(|cs| {
+                    if schedule(cs, task) {
+                        #[cfg(feature = "metrics")]
+                        task.header().metrics.record_wake(crate::metrics::WakeReason::Timer);
+                    }
+                })
+            });
 
             self.run_queue.dequeue_all(|p| {
                 let task = p.header();
@@ -411,9 +609,19 @@ impl Executor {
                 #[cfg(feature = "rtos-trace")]
                 trace::task_exec_begin(p.as_ptr() as u32);
 
+                #[cfg(feature = "metrics")]
+                let poll_start = Instant::now();
+
                 // Run the task
                 task.poll_fn.get().unwrap_unchecked()(p);
 
+                #[cfg(feature = "metrics")]
+                {
+                    let duration = Instant::now() - poll_start;
+                    task.metrics.record_poll(duration);
+                    crate::metrics::check_poll(p, duration);
+                }
+
                 #[cfg(feature = "rtos-trace")]
                 trace::task_exec_end();
 
@@ -456,23 +664,33 @@ impl Executor {
 /// You can obtain a `TaskRef` from a `Waker` using [`task_from_waker`].
 pub fn wake_task(task: TaskRef) {
     critical_section::with(|cs| {
-        let header = task.header();
-        let state = header.state.load(Ordering::Relaxed);
-
-        // If already scheduled, or if not started,
-        if (state & STATE_RUN_QUEUED != 0) || (state & STATE_SPAWNED == 0) {
-            return;
+        if schedule(cs, task) {
+            #[cfg(feature = "metrics")]
+            task.header().metrics.record_wake(crate::metrics::WakeReason::Waker);
         }
+    })
+}
 
-        // Mark it as scheduled
-        header.state.store(state | STATE_RUN_QUEUED, Ordering::Relaxed);
+/// Mark `task` as scheduled and enqueue it in its executor. Returns false, and does nothing, if
+/// it is already scheduled or not started.
+fn schedule(cs: CriticalSection, task: TaskRef) -> bool {
+    let header = task.header();
+    let state = header.state.load(Ordering::Relaxed);
 
-        // We have just marked the task as scheduled, so enqueue it.
-        unsafe {
-            let executor = header.executor.get().unwrap_unchecked();
-            executor.enqueue(cs, task);
-        }
-    })
+    // If already scheduled, or if not started,
+    if (state & STATE_RUN_QUEUED != 0) || (state & STATE_SPAWNED == 0) {
+        return false;
+    }
+
+    // Mark it as scheduled
+    header.state.store(state | STATE_RUN_QUEUED, Ordering::Relaxed);
+
+    // We have just marked the task as scheduled, so enqueue it.
+    unsafe {
+        let executor = header.executor.get().unwrap_unchecked();
+        executor.enqueue(cs, task);
+    }
+    true
 }
 
 #[cfg(feature = "integrated-timers")]
@@ -4,7 +4,9 @@ use core::ptr::NonNull;
 use atomic_polyfill::{AtomicPtr, Ordering};
 use critical_section::CriticalSection;
 
-use super::{TaskHeader, TaskRef};
+use super::{TaskHeader, TaskRef, MAX_PRIORITY};
+
+const LEVELS: usize = MAX_PRIORITY as usize + 1;
 
 pub(crate) struct RunQueueItem {
     next: AtomicPtr<TaskHeader>,
@@ -18,47 +20,67 @@ impl RunQueueItem {
     }
 }
 
-/// Atomic task queue using a very, very simple lock-free linked-list queue:
+/// Atomic task queue using a very, very simple lock-free linked-list queue per priority level:
 ///
-/// To enqueue a task, task.next is set to the old head, and head is atomically set to task.
+/// To enqueue a task, task.next is set to the old head of its priority level, and that head is
+/// atomically set to task.
 ///
-/// Dequeuing is done in batches: the queue is emptied by atomically replacing head with
-/// null. Then the batch is iterated following the next pointers until null is reached.
+/// Dequeuing is done in batches: the queue of a level is emptied by atomically replacing its head
+/// with null. Then the batch is iterated following the next pointers until null is reached. The
+/// levels are dequeued from the highest priority to the lowest, so higher priority tasks run first.
+/// A higher priority task enqueued while a batch is being processed runs before the next task of
+/// that batch.
 ///
 /// Note that batches will be iterated in the reverse order as they were enqueued. This is OK
 /// for our purposes: it can't create fairness problems since the next batch won't run until the
 /// current batch is completely processed, so even if a task enqueues itself instantly (for example
-/// by waking its own waker) can't prevent other tasks from running.
+/// by waking its own waker) can't prevent other tasks from running. A higher priority task that is
+/// always ready doesn't prevent them either, but it runs again after each lower priority task.
 pub(crate) struct RunQueue {
-    head: AtomicPtr<TaskHeader>,
+    heads: [AtomicPtr<TaskHeader>; LEVELS],
 }
 
 impl RunQueue {
     pub const fn new() -> Self {
-        Self {
-            head: AtomicPtr::new(ptr::null_mut()),
-        }
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicPtr<TaskHeader> = AtomicPtr::new(ptr::null_mut());
+        Self { heads: [EMPTY; LEVELS] }
     }
 
-    /// Enqueues an item. Returns true if the queue was empty.
+    /// Enqueues an item. Returns true if the queue of its priority level was empty.
     ///
     /// # Safety
     ///
     /// `item` must NOT be already enqueued in any queue.
     #[inline(always)]
     pub(crate) unsafe fn enqueue(&self, _cs: CriticalSection, task: TaskRef) -> bool {
-        let prev = self.head.load(Ordering::Relaxed);
+        let head = &self.heads[task.header().priority.get() as usize];
+        let prev = head.load(Ordering::Relaxed);
         task.header().run_queue_item.next.store(prev, Ordering::Relaxed);
-        self.head.store(task.as_ptr() as _, Ordering::Relaxed);
+        head.store(task.as_ptr() as _, Ordering::Relaxed);
         prev.is_null()
     }
 
-    /// Empty the queue, then call `on_task` for each task that was in the queue.
-    /// NOTE: It is OK for `on_task` to enqueue more tasks. In this case they're left in the queue
-    /// and will be processed by the *next* call to `dequeue_all`, *not* the current one.
+    /// Empty the queue, then call `on_task` for each task that was in the queue, starting
+    /// with the highest priority ones.
+    /// NOTE: It is OK for `on_task` to enqueue more tasks. If they have a lower priority than the
+    /// tasks being processed, they're processed by the current call to `dequeue_all`. If they have
+    /// a higher priority, they're processed before the next task of the current batch. Otherwise
+    /// they're left in the queue and will be processed by the *next* call.
     pub(crate) fn dequeue_all(&self, on_task: impl Fn(TaskRef)) {
-        // Atomically empty the queue.
-        let mut ptr = self.head.swap(ptr::null_mut(), Ordering::AcqRel);
+        for level in (0..LEVELS).rev() {
+            self.dequeue_level(level, &on_task);
+        }
+    }
+
+    /// Empty the queue of `level`, then call `on_task` for each task that was in it. Between two
+    /// tasks, the higher levels are checked, and their tasks are processed first.
+    ///
+    /// This always terminates: the higher levels are only dequeued after a task of this level, and
+    /// the batch of this level is finite.
+    fn dequeue_level(&self, level: usize, on_task: &impl Fn(TaskRef)) {
+        // Atomically empty the queue of this priority level.
+        let mut ptr = self.heads[level].swap(ptr::null_mut(), Ordering::AcqRel);
 
         // Iterate the linked list of tasks that were previously in the queue.
         while let Some(task) = NonNull::new(ptr) {
@@ -69,7 +91,72 @@ impl RunQueue {
 
             on_task(task);
 
+            // Tasks woken at a higher priority (by this task, or by an interrupt) don't wait for the
+            // rest of this batch.
+            for higher in (level + 1..LEVELS).rev() {
+                if !self.heads[higher].load(Ordering::Acquire).is_null() {
+                    self.dequeue_level(higher, on_task);
+                }
+            }
+
             ptr = next
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::poll_fn;
+    use core::task::{Poll, Waker};
+    use std::boxed::Box;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use crate::raw::test_util::executor;
+    use crate::raw::TaskStorage;
+
+    static POLLS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+    static WAKER: Mutex<Option<Waker>> = Mutex::new(None);
+
+    async fn log(name: &'static str) {
+        POLLS.lock().unwrap().push(name);
+    }
+
+    #[test]
+    fn higher_priority_runs_before_rest_of_batch() {
+        let (_lock, executor) = executor();
+        let spawner = executor.spawner();
+
+        // Waits for `wake` to be polled.
+        let high = Box::leak(Box::new(TaskStorage::new()));
+        let task = high.spawn(|| async {
+            let mut woken = false;
+            poll_fn(|cx| {
+                if woken {
+                    Poll::Ready(())
+                } else {
+                    woken = true;
+                    *WAKER.lock().unwrap() = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            })
+            .await;
+            log("high").await
+        });
+        spawner.spawn(task.with_priority(2)).unwrap();
+        unsafe { executor.poll() };
+
+        // Batches run in reverse order, so `wake` is polled before `low`.
+        let low = Box::leak(Box::new(TaskStorage::new()));
+        spawner.spawn(low.spawn(|| log("low"))).unwrap();
+        let wake = Box::leak(Box::new(TaskStorage::new()));
+        let task = wake.spawn(|| async {
+            log("wake").await;
+            WAKER.lock().unwrap().take().unwrap().wake();
+        });
+        spawner.spawn(task).unwrap();
+        unsafe { executor.poll() };
+
+        assert_eq!(*POLLS.lock().unwrap(), ["wake", "high", "low"]);
+    }
+}
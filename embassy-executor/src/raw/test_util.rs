@@ -0,0 +1,21 @@
+use std::boxed::Box;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::Executor;
+
+pub(crate) struct SharedExecutor(&'static Executor);
+
+// Only used by one test at a time.
+unsafe impl Send for SharedExecutor {}
+
+/// Get the executor shared by the tests, which take turns using it. Every executor allocates
+/// an alarm from the time driver, which only has a few.
+pub(crate) fn executor() -> (MutexGuard<'static, Option<SharedExecutor>>, &'static Executor) {
+    static EXECUTOR: Mutex<Option<SharedExecutor>> = Mutex::new(None);
+
+    let mut shared = EXECUTOR.lock().unwrap_or_else(PoisonError::into_inner);
+    let executor = shared
+        .get_or_insert_with(|| SharedExecutor(Box::leak(Box::new(Executor::new(|_| {}, core::ptr::null_mut())))))
+        .0;
+    (shared, executor)
+}
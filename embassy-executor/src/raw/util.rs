@@ -21,6 +21,10 @@ impl<T> UninitCell<T> {
         ptr::write(self.as_mut_ptr(), val)
     }
 
+    pub unsafe fn read(&self) -> T {
+        ptr::read(self.as_mut_ptr())
+    }
+
     pub unsafe fn drop_in_place(&self) {
         ptr::drop_in_place(self.as_mut_ptr())
     }
@@ -3,7 +3,8 @@ use core::marker::PhantomData;
 use core::mem;
 use core::task::Poll;
 
-use super::raw;
+use super::raw::util::UninitCell;
+use super::{raw, JoinHandle};
 
 /// Token to spawn a newly-created task in an executor.
 ///
@@ -15,20 +16,23 @@ use super::raw;
 /// in other threads or not. If `S: Send`, it can, which allows spawning it into a [`SendSpawner`].
 /// If not, it can't, so it can only be spawned into the current thread's executor, with [`Spawner`].
 ///
+/// The generic parameter `T` is the output of the task, which can be retrieved with the
+/// [`JoinHandle`] returned when spawning it.
+///
 /// # Panics
 ///
 /// Dropping a SpawnToken instance panics. You may not "abort" spawning a task in this way.
 /// Once you've invoked a task function and obtained a SpawnToken, you *must* spawn it.
 #[must_use = "Calling a task function does nothing on its own. You must spawn the returned SpawnToken, typically with Spawner::spawn()"]
-pub struct SpawnToken<S> {
-    raw_task: Option<raw::TaskRef>,
+pub struct SpawnToken<S, T = ()> {
+    raw_task: Option<(raw::TaskRef, *const UninitCell<T>)>,
     phantom: PhantomData<*mut S>,
 }
 
-impl<S> SpawnToken<S> {
-    pub(crate) unsafe fn new(raw_task: raw::TaskRef) -> Self {
+impl<S, T> SpawnToken<S, T> {
+    pub(crate) unsafe fn new(raw_task: raw::TaskRef, output: *const UninitCell<T>) -> Self {
         Self {
-            raw_task: Some(raw_task),
+            raw_task: Some((raw_task, output)),
             phantom: PhantomData,
         }
     }
@@ -39,9 +43,45 @@ impl<S> SpawnToken<S> {
             phantom: PhantomData,
         }
     }
+
+    /// Set the name of the task.
+    ///
+    /// The name is only kept with the `metrics` feature, to identify the task in the list
+    /// returned by `metrics::tasks()`. Tasks declared with `#[embassy_executor::task]` are
+    /// named after their function.
+    pub fn with_name(self, name: &'static str) -> Self {
+        #[cfg(feature = "metrics")]
+        if let Some((task, _)) = self.raw_task {
+            task.header().metrics.set_name(name);
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = name;
+        self
+    }
+
+    /// Set the priority of the task within its executor.
+    ///
+    /// When several tasks are ready to run, the executor polls the ones with a higher priority
+    /// first. Priorities go from 0, the default and lowest, to [`raw::MAX_PRIORITY`]. The
+    /// priority can also be set with `#[embassy_executor::task(priority = 2)]`.
+    ///
+    /// Note the executor doesn't preempt a task to poll a higher priority one. To get this,
+    /// run the higher priority tasks in a separate, interrupt-driven executor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `priority` is higher than [`raw::MAX_PRIORITY`].
+    pub fn with_priority(self, priority: u8) -> Self {
+        assert!(priority <= raw::MAX_PRIORITY, "task priority is too high");
+        if let Some((task, _)) = self.raw_task {
+            // The task is not spawned in an executor yet, so it's not in a run queue.
+            task.header().priority.set(priority);
+        }
+        self
+    }
 }
 
-impl<S> Drop for SpawnToken<S> {
+impl<S, T> Drop for SpawnToken<S, T> {
     fn drop(&mut self) {
         // TODO deallocate the task instead.
         panic!("SpawnToken instances may not be dropped. You must pass them to Spawner::spawn()")
@@ -100,14 +140,20 @@ impl Spawner {
     /// Spawn a task into an executor.
     ///
     /// You obtain the `token` by calling a task function (i.e. one marked with `#[embassy_executor::task]`).
-    pub fn spawn<S>(&self, token: SpawnToken<S>) -> Result<(), SpawnError> {
+    ///
+    /// The returned [`JoinHandle`] can be used to wait for the task to finish and get its output.
+    /// It may be dropped if you're not interested in that.
+    pub fn spawn<S, T>(&self, token: SpawnToken<S, T>) -> Result<JoinHandle<T>, SpawnError> {
         let task = token.raw_task;
         mem::forget(token);
 
         match task {
-            Some(task) => {
+            Some((task, output)) => {
+                // The handle is created first, to refer to this run of the task even if the
+                // executor runs it right away.
+                let handle = JoinHandle::new(task, output);
                 unsafe { self.executor.spawn(task) };
-                Ok(())
+                Ok(handle)
             }
             None => Err(SpawnError::Busy),
         }
@@ -122,8 +168,8 @@ impl Spawner {
     /// # Panics
     ///
     /// Panics if the spawning fails.
-    pub fn must_spawn<S>(&self, token: SpawnToken<S>) {
-        unwrap!(self.spawn(token));
+    pub fn must_spawn<S, T>(&self, token: SpawnToken<S, T>) -> JoinHandle<T> {
+        unwrap!(self.spawn(token))
     }
 
     /// Convert this Spawner to a SendSpawner. This allows you to send the
@@ -176,14 +222,18 @@ impl SendSpawner {
     /// Spawn a task into an executor.
     ///
     /// You obtain the `token` by calling a task function (i.e. one marked with `#[embassy_executor::task]`).
-    pub fn spawn<S: Send>(&self, token: SpawnToken<S>) -> Result<(), SpawnError> {
+    ///
+    /// The returned [`JoinHandle`] can be used to wait for the task to finish and get its output.
+    /// It may be dropped if you're not interested in that.
+    pub fn spawn<S: Send, T: Send>(&self, token: SpawnToken<S, T>) -> Result<JoinHandle<T>, SpawnError> {
         let header = token.raw_task;
         mem::forget(token);
 
         match header {
-            Some(header) => {
+            Some((header, output)) => {
+                let handle = JoinHandle::new(header, output);
                 unsafe { self.executor.spawn(header) };
-                Ok(())
+                Ok(handle)
             }
             None => Err(SpawnError::Busy),
         }
@@ -194,7 +244,7 @@ impl SendSpawner {
     /// # Panics
     ///
     /// Panics if the spawning fails.
-    pub fn must_spawn<S: Send>(&self, token: SpawnToken<S>) {
-        unwrap!(self.spawn(token));
+    pub fn must_spawn<S: Send, T: Send>(&self, token: SpawnToken<S, T>) -> JoinHandle<T> {
+        unwrap!(self.spawn(token))
     }
 }
@@ -8,7 +8,8 @@ mod util;
 use macros::*;
 
 /// Declares an async task that can be run by `embassy-executor`. The optional `pool_size` parameter can be used to specify how
-/// many concurrent tasks can be spawned (default is 1) for the function.
+/// many concurrent tasks can be spawned (default is 1) for the function. The optional `priority` parameter sets the priority
+/// of the task within its executor (default is 0, the lowest).
 ///
 ///
 /// The following restrictions apply:
@@ -16,6 +17,7 @@ use macros::*;
 /// * The function must be declared `async`.
 /// * The function must not use generics.
 /// * The optional `pool_size` attribute must be 1 or greater.
+/// * The optional `priority` attribute must not be higher than `embassy_executor::raw::MAX_PRIORITY`.
 ///
 ///
 /// ## Examples
@@ -37,6 +39,15 @@ use macros::*;
 ///     // Function body
 /// }
 /// ```
+///
+/// Declaring a task polled before the lower priority ones when they're ready at the same time:
+///
+/// ``` rust
+/// #[embassy_executor::task(priority = 2)]
+/// async fn mytask() {
+///     // Function body
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn task(args: TokenStream, item: TokenStream) -> TokenStream {
     let args = syn::parse_macro_input!(args as syn::AttributeArgs);
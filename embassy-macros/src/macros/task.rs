@@ -9,6 +9,8 @@ use crate::util::ctxt::Ctxt;
 struct Args {
     #[darling(default)]
     pool_size: Option<usize>,
+    #[darling(default)]
+    priority: Option<u8>,
 }
 
 pub fn run(args: syn::AttributeArgs, f: syn::ItemFn) -> Result<TokenStream, TokenStream> {
@@ -33,17 +35,14 @@ pub fn run(args: syn::AttributeArgs, f: syn::ItemFn) -> Result<TokenStream, Toke
     if !f.sig.variadic.is_none() {
         ctxt.error_spanned_by(&f.sig, "task functions must not be variadic");
     }
-    match &f.sig.output {
-        ReturnType::Default => {}
+    // The output of the task, which the JoinHandle returns. Tasks that never return have a `()` output.
+    let (output, never) = match &f.sig.output {
+        ReturnType::Default => (quote!(()), false),
         ReturnType::Type(_, ty) => match &**ty {
-            Type::Tuple(tuple) if tuple.elems.is_empty() => {}
-            Type::Never(_) => {}
-            _ => ctxt.error_spanned_by(
-                &f.sig,
-                "task functions must either not return a value, return `()` or return `!`",
-            ),
+            Type::Never(_) => (quote!(()), true),
+            ty => (quote!(#ty), false),
         },
-    }
+    };
 
     if pool_size < 1 {
         ctxt.error_spanned_by(&f.sig, "pool_size must be 1 or greater");
@@ -78,12 +77,27 @@ pub fn run(args: syn::AttributeArgs, f: syn::ItemFn) -> Result<TokenStream, Toke
     let visibility = task_inner.vis.clone();
     task_inner.vis = syn::Visibility::Inherited;
     task_inner.sig.ident = task_inner_ident.clone();
+    if never {
+        // `!` can't be used as a type parameter on stable, so the task has a `()` output instead.
+        task_inner.sig.output = ReturnType::Default;
+    }
+
+    let task_name = task_ident.to_string();
+    let mut spawn = quote! {
+        unsafe { POOL._spawn_async_fn(move || #task_inner_ident(#(#arg_names,)*)) }.with_name(#task_name)
+    };
+    if let Some(priority) = args.priority {
+        spawn = quote! {
+            const _: () = ::core::assert!(#priority <= ::embassy_executor::raw::MAX_PRIORITY, "task priority is too high");
+            #spawn.with_priority(#priority)
+        };
+    }
 
     let mut task_outer: ItemFn = parse_quote! {
-        #visibility fn #task_ident(#fargs) -> ::embassy_executor::SpawnToken<impl Sized> {
-            type Fut = impl ::core::future::Future + 'static;
+        #visibility fn #task_ident(#fargs) -> ::embassy_executor::SpawnToken<impl Sized, #output> {
+            type Fut = impl ::core::future::Future<Output = #output> + 'static;
             static POOL: ::embassy_executor::raw::TaskPool<Fut, #pool_size> = ::embassy_executor::raw::TaskPool::new();
-            unsafe { POOL._spawn_async_fn(move || #task_inner_ident(#(#arg_names,)*)) }
+            #spawn
         }
     };
 
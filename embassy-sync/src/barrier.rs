@@ -0,0 +1,185 @@
+//! A synchronization primitive that lets a group of tasks wait for each other.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A barrier that makes `N` tasks wait until all of them have reached it.
+///
+/// When the `N`th task calls [`wait`](Barrier::wait), all waiting tasks are released, and the
+/// barrier can be used again. Exactly one of the released tasks is the leader, which can be used
+/// to do something once per round, like logging that all tasks are initialized.
+///
+/// ## Example
+///
+/// ```
+/// # use embassy_sync::barrier::Barrier;
+/// # use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// # use futures_executor::block_on;
+/// # let test = async {
+/// let barrier = Barrier::<NoopRawMutex, 2>::new();
+///
+/// let (a, b) = futures_util::join!(barrier.wait(), barrier.wait());
+/// // The last task to arrive is the leader
+/// assert!(!a.is_leader());
+/// assert!(b.is_leader());
+/// # };
+/// #
+/// # block_on(test);
+/// ```
+pub struct Barrier<M: RawMutex, const N: usize> {
+    inner: Mutex<M, RefCell<State<N>>>,
+}
+
+struct State<const N: usize> {
+    /// The amount of tasks waiting in the current round
+    count: usize,
+    /// Incremented every time the waiting tasks are released
+    generation: u64,
+    wakers: MultiWakerRegistration<N>,
+}
+
+/// The result of [`Barrier::wait`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BarrierWaitResult {
+    is_leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Returns whether this task is the leader of its round. Only one task per round is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.is_leader
+    }
+}
+
+impl<M: RawMutex, const N: usize> Barrier<M, N> {
+    /// Create a new `Barrier` for `N` tasks.
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(State {
+                count: 0,
+                generation: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Wait until `N` tasks are waiting on the barrier.
+    ///
+    /// The task that completes the round is the leader. If this future is dropped before all tasks
+    /// arrived, it no longer counts towards the round.
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let generation = self.inner.lock(|s| {
+            let mut s = s.borrow_mut();
+            s.count += 1;
+            if s.count == N {
+                s.count = 0;
+                s.generation += 1;
+                s.wakers.wake();
+                None
+            } else {
+                Some(s.generation)
+            }
+        });
+
+        let Some(generation) = generation else {
+            return BarrierWaitResult { is_leader: true };
+        };
+
+        let mut waiting = Waiting {
+            barrier: self,
+            generation,
+            done: false,
+        };
+
+        poll_fn(|cx| {
+            self.inner.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.generation != generation {
+                    waiting.done = true;
+                    Poll::Ready(BarrierWaitResult { is_leader: false })
+                } else {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+/// Removes a cancelled task from the round of a [`Barrier`].
+struct Waiting<'a, M: RawMutex, const N: usize> {
+    barrier: &'a Barrier<M, N>,
+    generation: u64,
+    done: bool,
+}
+
+impl<'a, M: RawMutex, const N: usize> Drop for Waiting<'a, M, N> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        self.barrier.inner.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.generation == self.generation {
+                s.count -= 1;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+
+    use super::*;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    #[futures_test::test]
+    async fn releases_all_tasks() {
+        let executor = ThreadPool::new().unwrap();
+
+        static BARRIER: Barrier<CriticalSectionRawMutex, 3> = Barrier::new();
+
+        for _ in 0..2 {
+            let a = executor.spawn_with_handle(BARRIER.wait()).unwrap();
+            let b = executor.spawn_with_handle(BARRIER.wait()).unwrap();
+
+            Delay::new(Duration::from_millis(100)).await;
+            let c = BARRIER.wait().await;
+
+            // Exactly one leader per round
+            let leaders = [a.await, b.await, c].iter().filter(|r| r.is_leader()).count();
+            assert_eq!(leaders, 1);
+        }
+    }
+
+    #[futures_test::test]
+    async fn cancelled_wait() {
+        let barrier = Barrier::<NoopRawMutex, 2>::new();
+
+        {
+            let wait = barrier.wait();
+            futures_util::pin_mut!(wait);
+            assert!(futures_util::poll!(wait.as_mut()).is_pending());
+        }
+
+        // The cancelled task doesn't count, so this one has to wait
+        let wait = barrier.wait();
+        futures_util::pin_mut!(wait);
+        assert!(futures_util::poll!(wait.as_mut()).is_pending());
+        assert!(barrier.wait().await.is_leader());
+        assert!(!wait.await.is_leader());
+    }
+}
@@ -17,9 +17,18 @@
 //! messages that it can store, and if this limit is reached, trying to send
 //! another message will result in an error being returned.
 //!
+//! A channel can be closed with [`Channel::close`]. To close it automatically once all producers
+//! or all consumers are gone, use the counted handles returned by [`Channel::counted_sender`] and
+//! [`Channel::counted_receiver`]. Sending to a closed channel and receiving from a closed, empty
+//! channel fails.
+//!
+//! Besides single messages, messages can be sent and received in batches with
+//! [`Channel::send_all`] and [`Channel::recv_many`], and the next message can be inspected
+//! without removing it with [`Channel::peek`].
+//!
 
 use core::cell::RefCell;
-use core::future::Future;
+use core::future::{poll_fn, Future};
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
@@ -64,12 +73,75 @@ where
     pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
         self.channel.try_send(message)
     }
+
+    /// Send all values of an iterator, waiting for capacity as needed.
+    ///
+    /// See [`Channel::send_all()`]
+    pub async fn send_all(&self, messages: impl IntoIterator<Item = T>) -> Result<(), SendError<T>> {
+        self.channel.send_all(messages).await
+    }
+
+    /// Returns the number of messages in the channel.
+    ///
+    /// See [`Channel::len()`]
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Returns whether the channel is empty.
+    ///
+    /// See [`Channel::is_empty()`]
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Returns whether the channel is full.
+    ///
+    /// See [`Channel::is_full()`]
+    pub fn is_full(&self) -> bool {
+        self.channel.is_full()
+    }
+
+    /// Returns the maximum number of messages the channel can hold.
+    ///
+    /// See [`Channel::capacity()`]
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity()
+    }
+
+    /// Returns the number of messages that can be sent before the channel is full.
+    ///
+    /// See [`Channel::free_capacity()`]
+    pub fn free_capacity(&self) -> usize {
+        self.channel.free_capacity()
+    }
+
+    /// Remove all messages from the channel.
+    ///
+    /// See [`Channel::clear()`]
+    pub fn clear(&self) {
+        self.channel.clear()
+    }
+
+    /// Close the channel.
+    ///
+    /// See [`Channel::close()`]
+    pub fn close(&self) {
+        self.channel.close()
+    }
+
+    /// Returns whether the channel is closed.
+    ///
+    /// See [`Channel::is_closed()`]
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
 }
 
 /// Send-only access to a [`Channel`] without knowing channel size.
 #[derive(Copy)]
 pub struct DynamicSender<'ch, T> {
-    channel: &'ch dyn DynamicChannel<T>,
+    pub(crate) channel: &'ch dyn DynamicChannel<T>,
 }
 
 impl<'ch, T> Clone for DynamicSender<'ch, T> {
@@ -104,6 +176,71 @@ impl<'ch, T> DynamicSender<'ch, T> {
     pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
         self.channel.try_send_with_context(message, None)
     }
+
+    /// Send all values of an iterator, waiting for capacity as needed.
+    ///
+    /// See [`Channel::send_all()`]
+    pub async fn send_all(&self, messages: impl IntoIterator<Item = T>) -> Result<(), SendError<T>> {
+        let mut messages = messages.into_iter();
+        let mut pending = None;
+        poll_fn(|cx| poll_send_all(self.channel, &mut pending, &mut messages, cx)).await
+    }
+
+    /// Returns the number of messages in the channel.
+    ///
+    /// See [`Channel::len()`]
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Returns whether the channel is empty.
+    ///
+    /// See [`Channel::is_empty()`]
+    pub fn is_empty(&self) -> bool {
+        self.channel.len() == 0
+    }
+
+    /// Returns whether the channel is full.
+    ///
+    /// See [`Channel::is_full()`]
+    pub fn is_full(&self) -> bool {
+        self.channel.len() == self.channel.capacity()
+    }
+
+    /// Returns the maximum number of messages the channel can hold.
+    ///
+    /// See [`Channel::capacity()`]
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity()
+    }
+
+    /// Returns the number of messages that can be sent before the channel is full.
+    ///
+    /// See [`Channel::free_capacity()`]
+    pub fn free_capacity(&self) -> usize {
+        self.channel.capacity() - self.channel.len()
+    }
+
+    /// Remove all messages from the channel.
+    ///
+    /// See [`Channel::clear()`]
+    pub fn clear(&self) {
+        self.channel.clear()
+    }
+
+    /// Close the channel.
+    ///
+    /// See [`Channel::close()`]
+    pub fn close(&self) {
+        self.channel.close()
+    }
+
+    /// Returns whether the channel is closed.
+    ///
+    /// See [`Channel::is_closed()`]
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
 }
 
 /// Receive-only access to a [`Channel`].
@@ -141,12 +278,102 @@ where
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         self.channel.try_recv()
     }
+
+    /// Receive multiple values, waiting until at least one is available.
+    ///
+    /// See [`Channel::recv_many()`]
+    pub async fn recv_many(&self, buf: &mut [T]) -> Result<usize, RecvError> {
+        self.channel.recv_many(buf).await
+    }
+
+    /// Attempt to immediately receive multiple values.
+    ///
+    /// See [`Channel::try_recv_many()`]
+    pub fn try_recv_many(&self, buf: &mut [T]) -> Result<usize, TryRecvError> {
+        self.channel.try_recv_many(buf)
+    }
+
+    /// Wait for the next value without removing it from the channel.
+    ///
+    /// See [`Channel::peek()`]
+    pub async fn peek(&self) -> Result<T, RecvError>
+    where
+        T: Clone,
+    {
+        self.channel.peek().await
+    }
+
+    /// Attempt to immediately get the next value without removing it from the channel.
+    ///
+    /// See [`Channel::try_peek()`]
+    pub fn try_peek(&self) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        self.channel.try_peek()
+    }
+
+    /// Returns the number of messages in the channel.
+    ///
+    /// See [`Channel::len()`]
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Returns whether the channel is empty.
+    ///
+    /// See [`Channel::is_empty()`]
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Returns whether the channel is full.
+    ///
+    /// See [`Channel::is_full()`]
+    pub fn is_full(&self) -> bool {
+        self.channel.is_full()
+    }
+
+    /// Returns the maximum number of messages the channel can hold.
+    ///
+    /// See [`Channel::capacity()`]
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity()
+    }
+
+    /// Returns the number of messages that can be sent before the channel is full.
+    ///
+    /// See [`Channel::free_capacity()`]
+    pub fn free_capacity(&self) -> usize {
+        self.channel.free_capacity()
+    }
+
+    /// Remove all messages from the channel.
+    ///
+    /// See [`Channel::clear()`]
+    pub fn clear(&self) {
+        self.channel.clear()
+    }
+
+    /// Close the channel.
+    ///
+    /// See [`Channel::close()`]
+    pub fn close(&self) {
+        self.channel.close()
+    }
+
+    /// Returns whether the channel is closed.
+    ///
+    /// See [`Channel::is_closed()`]
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
 }
 
 /// Receive-only access to a [`Channel`] without knowing channel size.
 #[derive(Copy)]
 pub struct DynamicReceiver<'ch, T> {
-    channel: &'ch dyn DynamicChannel<T>,
+    pub(crate) channel: &'ch dyn DynamicChannel<T>,
 }
 
 impl<'ch, T> Clone for DynamicReceiver<'ch, T> {
@@ -169,6 +396,96 @@ impl<'ch, T> DynamicReceiver<'ch, T> {
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         self.channel.try_recv_with_context(None)
     }
+
+    /// Receive multiple values, waiting until at least one is available.
+    ///
+    /// See [`Channel::recv_many()`]
+    pub async fn recv_many(&self, buf: &mut [T]) -> Result<usize, RecvError> {
+        poll_fn(|cx| poll_recv(self.channel.try_recv_many_with_context(buf, Some(cx)))).await
+    }
+
+    /// Attempt to immediately receive multiple values.
+    ///
+    /// See [`Channel::try_recv_many()`]
+    pub fn try_recv_many(&self, buf: &mut [T]) -> Result<usize, TryRecvError> {
+        self.channel.try_recv_many_with_context(buf, None)
+    }
+
+    /// Wait for the next value without removing it from the channel.
+    ///
+    /// See [`Channel::peek()`]
+    pub async fn peek(&self) -> Result<T, RecvError>
+    where
+        T: Clone,
+    {
+        poll_fn(|cx| poll_recv(self.channel.try_peek_with_context(Some(cx)))).await
+    }
+
+    /// Attempt to immediately get the next value without removing it from the channel.
+    ///
+    /// See [`Channel::try_peek()`]
+    pub fn try_peek(&self) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        self.channel.try_peek_with_context(None)
+    }
+
+    /// Returns the number of messages in the channel.
+    ///
+    /// See [`Channel::len()`]
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Returns whether the channel is empty.
+    ///
+    /// See [`Channel::is_empty()`]
+    pub fn is_empty(&self) -> bool {
+        self.channel.len() == 0
+    }
+
+    /// Returns whether the channel is full.
+    ///
+    /// See [`Channel::is_full()`]
+    pub fn is_full(&self) -> bool {
+        self.channel.len() == self.channel.capacity()
+    }
+
+    /// Returns the maximum number of messages the channel can hold.
+    ///
+    /// See [`Channel::capacity()`]
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity()
+    }
+
+    /// Returns the number of messages that can be sent before the channel is full.
+    ///
+    /// See [`Channel::free_capacity()`]
+    pub fn free_capacity(&self) -> usize {
+        self.channel.capacity() - self.channel.len()
+    }
+
+    /// Remove all messages from the channel.
+    ///
+    /// See [`Channel::clear()`]
+    pub fn clear(&self) {
+        self.channel.clear()
+    }
+
+    /// Close the channel.
+    ///
+    /// See [`Channel::close()`]
+    pub fn close(&self) {
+        self.channel.close()
+    }
+
+    /// Returns whether the channel is closed.
+    ///
+    /// See [`Channel::is_closed()`]
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
 }
 
 impl<'ch, M, T, const N: usize> From<Receiver<'ch, M, T, N>> for DynamicReceiver<'ch, T>
@@ -180,6 +497,151 @@ where
     }
 }
 
+/// Send-only access to a [`Channel`] that closes the channel when the last one is dropped.
+///
+/// Counted senders are created with [`Channel::counted_sender`] and cloned with [`Clone`].
+pub struct CountedSender<'ch, M, T, const N: usize>
+where
+    M: RawMutex,
+{
+    channel: &'ch Channel<M, T, N>,
+}
+
+impl<'ch, M, T, const N: usize> Clone for CountedSender<'ch, M, T, N>
+where
+    M: RawMutex,
+{
+    fn clone(&self) -> Self {
+        self.channel.register_sender();
+        CountedSender { channel: self.channel }
+    }
+}
+
+impl<'ch, M, T, const N: usize> Drop for CountedSender<'ch, M, T, N>
+where
+    M: RawMutex,
+{
+    fn drop(&mut self) {
+        self.channel.unregister_sender()
+    }
+}
+
+impl<'ch, M, T, const N: usize> CountedSender<'ch, M, T, N>
+where
+    M: RawMutex,
+{
+    /// Sends a value.
+    ///
+    /// See [`Channel::send()`]
+    pub fn send(&self, message: T) -> SendFuture<'ch, M, T, N> {
+        self.channel.send(message)
+    }
+
+    /// Attempt to immediately send a message.
+    ///
+    /// See [`Channel::try_send()`]
+    pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
+        self.channel.try_send(message)
+    }
+
+    /// Send all values of an iterator, waiting for capacity as needed.
+    ///
+    /// See [`Channel::send_all()`]
+    pub async fn send_all(&self, messages: impl IntoIterator<Item = T>) -> Result<(), SendError<T>> {
+        self.channel.send_all(messages).await
+    }
+
+    /// Close the channel.
+    ///
+    /// See [`Channel::close()`]
+    pub fn close(&self) {
+        self.channel.close()
+    }
+
+    /// Returns whether the channel is closed.
+    ///
+    /// See [`Channel::is_closed()`]
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
+}
+
+/// Receive-only access to a [`Channel`] that closes the channel when the last one is dropped.
+///
+/// Counted receivers are created with [`Channel::counted_receiver`] and cloned with [`Clone`].
+pub struct CountedReceiver<'ch, M, T, const N: usize>
+where
+    M: RawMutex,
+{
+    channel: &'ch Channel<M, T, N>,
+}
+
+impl<'ch, M, T, const N: usize> Clone for CountedReceiver<'ch, M, T, N>
+where
+    M: RawMutex,
+{
+    fn clone(&self) -> Self {
+        self.channel.register_receiver();
+        CountedReceiver { channel: self.channel }
+    }
+}
+
+impl<'ch, M, T, const N: usize> Drop for CountedReceiver<'ch, M, T, N>
+where
+    M: RawMutex,
+{
+    fn drop(&mut self) {
+        self.channel.unregister_receiver()
+    }
+}
+
+impl<'ch, M, T, const N: usize> CountedReceiver<'ch, M, T, N>
+where
+    M: RawMutex,
+{
+    /// Receive the next value.
+    ///
+    /// See [`Channel::recv()`].
+    pub fn recv(&self) -> RecvFuture<'_, M, T, N> {
+        self.channel.recv()
+    }
+
+    /// Attempt to immediately receive the next value.
+    ///
+    /// See [`Channel::try_recv()`]
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.channel.try_recv()
+    }
+
+    /// Receive multiple values, waiting until at least one is available.
+    ///
+    /// See [`Channel::recv_many()`]
+    pub async fn recv_many(&self, buf: &mut [T]) -> Result<usize, RecvError> {
+        self.channel.recv_many(buf).await
+    }
+
+    /// Attempt to immediately receive multiple values.
+    ///
+    /// See [`Channel::try_recv_many()`]
+    pub fn try_recv_many(&self, buf: &mut [T]) -> Result<usize, TryRecvError> {
+        self.channel.try_recv_many(buf)
+    }
+
+    /// Close the channel.
+    ///
+    /// See [`Channel::close()`]
+    pub fn close(&self) {
+        self.channel.close()
+    }
+
+    /// Returns whether the channel is closed.
+    ///
+    /// See [`Channel::is_closed()`]
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
+}
+
 /// Future returned by [`Channel::recv`] and  [`Receiver::recv`].
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 pub struct RecvFuture<'ch, M, T, const N: usize>
@@ -193,13 +655,10 @@ impl<'ch, M, T, const N: usize> Future for RecvFuture<'ch, M, T, N>
 where
     M: RawMutex,
 {
-    type Output = T;
+    type Output = Result<T, RecvError>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
-        match self.channel.try_recv_with_context(Some(cx)) {
-            Ok(v) => Poll::Ready(v),
-            Err(TryRecvError::Empty) => Poll::Pending,
-        }
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        poll_recv(self.channel.try_recv_with_context(Some(cx)))
     }
 }
 
@@ -210,13 +669,10 @@ pub struct DynamicRecvFuture<'ch, T> {
 }
 
 impl<'ch, T> Future for DynamicRecvFuture<'ch, T> {
-    type Output = T;
+    type Output = Result<T, RecvError>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
-        match self.channel.try_recv_with_context(Some(cx)) {
-            Ok(v) => Poll::Ready(v),
-            Err(TryRecvError::Empty) => Poll::Pending,
-        }
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        poll_recv(self.channel.try_recv_with_context(Some(cx)))
     }
 }
 
@@ -234,16 +690,17 @@ impl<'ch, M, T, const N: usize> Future for SendFuture<'ch, M, T, N>
 where
     M: RawMutex,
 {
-    type Output = ();
+    type Output = Result<(), SendError<T>>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.message.take() {
             Some(m) => match self.channel.try_send_with_context(m, Some(cx)) {
-                Ok(..) => Poll::Ready(()),
+                Ok(..) => Poll::Ready(Ok(())),
                 Err(TrySendError::Full(m)) => {
                     self.message = Some(m);
                     Poll::Pending
                 }
+                Err(TrySendError::Closed(m)) => Poll::Ready(Err(SendError(m))),
             },
             None => panic!("Message cannot be None"),
         }
@@ -260,16 +717,17 @@ pub struct DynamicSendFuture<'ch, T> {
 }
 
 impl<'ch, T> Future for DynamicSendFuture<'ch, T> {
-    type Output = ();
+    type Output = Result<(), SendError<T>>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.message.take() {
             Some(m) => match self.channel.try_send_with_context(m, Some(cx)) {
-                Ok(..) => Poll::Ready(()),
+                Ok(..) => Poll::Ready(Ok(())),
                 Err(TrySendError::Full(m)) => {
                     self.message = Some(m);
                     Poll::Pending
                 }
+                Err(TrySendError::Closed(m)) => Poll::Ready(Err(SendError(m))),
             },
             None => panic!("Message cannot be None"),
         }
@@ -278,10 +736,62 @@ impl<'ch, T> Future for DynamicSendFuture<'ch, T> {
 
 impl<'ch, T> Unpin for DynamicSendFuture<'ch, T> {}
 
-trait DynamicChannel<T> {
+pub(crate) trait DynamicChannel<T> {
     fn try_send_with_context(&self, message: T, cx: Option<&mut Context<'_>>) -> Result<(), TrySendError<T>>;
 
     fn try_recv_with_context(&self, cx: Option<&mut Context<'_>>) -> Result<T, TryRecvError>;
+
+    fn try_recv_many_with_context(&self, buf: &mut [T], cx: Option<&mut Context<'_>>) -> Result<usize, TryRecvError>;
+
+    fn try_peek_with_context(&self, cx: Option<&mut Context<'_>>) -> Result<T, TryRecvError>
+    where
+        T: Clone;
+
+    fn len(&self) -> usize;
+
+    fn capacity(&self) -> usize;
+
+    fn clear(&self);
+
+    fn close(&self);
+
+    fn is_closed(&self) -> bool;
+}
+
+/// Turn the result of a non-blocking receive into the poll result of a blocking one.
+pub(crate) fn poll_recv<R>(result: Result<R, TryRecvError>) -> Poll<Result<R, RecvError>> {
+    match result {
+        Ok(v) => Poll::Ready(Ok(v)),
+        Err(TryRecvError::Empty) => Poll::Pending,
+        Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+    }
+}
+
+/// Send messages until the iterator is exhausted or the channel is full.
+///
+/// Messages are taken from the iterator outside the channel's lock, and pushed one at a time.
+/// A message that did not fit is kept in `pending` for the next poll.
+pub(crate) fn poll_send_all<T>(
+    channel: &dyn DynamicChannel<T>,
+    pending: &mut Option<T>,
+    messages: &mut impl Iterator<Item = T>,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), SendError<T>>> {
+    loop {
+        let message = match pending.take().or_else(|| messages.next()) {
+            Some(message) => message,
+            None => return Poll::Ready(Ok(())),
+        };
+
+        match channel.try_send_with_context(message, Some(cx)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(message)) => {
+                *pending = Some(message);
+                return Poll::Pending;
+            }
+            Err(TrySendError::Closed(message)) => return Poll::Ready(Err(SendError(message))),
+        }
+    }
 }
 
 /// Error returned by [`try_recv`](Channel::try_recv).
@@ -290,8 +800,17 @@ trait DynamicChannel<T> {
 pub enum TryRecvError {
     /// A message could not be received because the channel is empty.
     Empty,
+    /// A message could not be received because the channel is empty and closed.
+    Closed,
 }
 
+/// Error returned by [`recv`](Channel::recv).
+///
+/// The channel is closed and all messages have been received.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RecvError;
+
 /// Error returned by [`try_send`](Channel::try_send).
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -299,12 +818,70 @@ pub enum TrySendError<T> {
     /// The data could not be sent on the channel because the channel is
     /// currently full and sending would require blocking.
     Full(T),
+    /// The data could not be sent because the channel is closed.
+    Closed(T),
+}
+
+/// Error returned by [`send`](Channel::send).
+///
+/// The channel is closed. The message that could not be sent is handed back.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SendError<T>(pub T);
+
+/// Sender/receiver bookkeeping of a channel.
+///
+/// Shared between the channel flavors so they close under the same conditions.
+pub(crate) struct Handles {
+    senders: usize,
+    receivers: usize,
+    closed: bool,
+}
+
+impl Handles {
+    pub(crate) const fn new() -> Self {
+        Self {
+            senders: 0,
+            receivers: 0,
+            closed: false,
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Mark the channel as closed. Returns `true` if it was open before.
+    pub(crate) fn close(&mut self) -> bool {
+        !core::mem::replace(&mut self.closed, true)
+    }
+
+    pub(crate) fn register_sender(&mut self) {
+        self.senders += 1;
+    }
+
+    /// Returns `true` if this closed the channel.
+    pub(crate) fn unregister_sender(&mut self) -> bool {
+        self.senders -= 1;
+        self.senders == 0 && self.close()
+    }
+
+    pub(crate) fn register_receiver(&mut self) {
+        self.receivers += 1;
+    }
+
+    /// Returns `true` if this closed the channel.
+    pub(crate) fn unregister_receiver(&mut self) -> bool {
+        self.receivers -= 1;
+        self.receivers == 0 && self.close()
+    }
 }
 
 struct ChannelState<T, const N: usize> {
     queue: Deque<T, N>,
     receiver_waker: WakerRegistration,
     senders_waker: WakerRegistration,
+    handles: Handles,
 }
 
 impl<T, const N: usize> ChannelState<T, N> {
@@ -313,6 +890,7 @@ impl<T, const N: usize> ChannelState<T, N> {
             queue: Deque::new(),
             receiver_waker: WakerRegistration::new(),
             senders_waker: WakerRegistration::new(),
+            handles: Handles::new(),
         }
     }
 
@@ -327,6 +905,8 @@ impl<T, const N: usize> ChannelState<T, N> {
 
         if let Some(message) = self.queue.pop_front() {
             Ok(message)
+        } else if self.handles.is_closed() {
+            Err(TryRecvError::Closed)
         } else {
             if let Some(cx) = cx {
                 self.receiver_waker.register(cx.waker());
@@ -340,6 +920,10 @@ impl<T, const N: usize> ChannelState<T, N> {
     }
 
     fn try_send_with_context(&mut self, message: T, cx: Option<&mut Context<'_>>) -> Result<(), TrySendError<T>> {
+        if self.handles.is_closed() {
+            return Err(TrySendError::Closed(message));
+        }
+
         match self.queue.push_back(message) {
             Ok(()) => {
                 self.receiver_waker.wake();
@@ -353,6 +937,86 @@ impl<T, const N: usize> ChannelState<T, N> {
             }
         }
     }
+
+    fn try_recv_many_with_context(
+        &mut self,
+        buf: &mut [T],
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<usize, TryRecvError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.queue.is_full() {
+            self.senders_waker.wake();
+        }
+
+        let mut received = 0;
+        for slot in buf.iter_mut() {
+            match self.queue.pop_front() {
+                Some(message) => *slot = message,
+                None => break,
+            }
+            received += 1;
+        }
+
+        if received > 0 {
+            Ok(received)
+        } else if self.handles.is_closed() {
+            Err(TryRecvError::Closed)
+        } else {
+            if let Some(cx) = cx {
+                self.receiver_waker.register(cx.waker());
+            }
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    fn try_peek_with_context(&mut self, cx: Option<&mut Context<'_>>) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        if let Some(message) = self.queue.front() {
+            Ok(message.clone())
+        } else if self.handles.is_closed() {
+            Err(TryRecvError::Closed)
+        } else {
+            if let Some(cx) = cx {
+                self.receiver_waker.register(cx.waker());
+            }
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    fn clear(&mut self) {
+        if self.queue.is_full() {
+            self.senders_waker.wake();
+        }
+        self.queue.clear();
+    }
+
+    fn wake_all(&mut self) {
+        self.receiver_waker.wake();
+        self.senders_waker.wake();
+    }
+
+    fn close(&mut self) {
+        if self.handles.close() {
+            self.wake_all();
+        }
+    }
+
+    fn unregister_sender(&mut self) {
+        if self.handles.unregister_sender() {
+            self.wake_all();
+        }
+    }
+
+    fn unregister_receiver(&mut self) {
+        if self.handles.unregister_receiver() {
+            self.wake_all();
+        }
+    }
 }
 
 /// A bounded channel for communicating between asynchronous tasks
@@ -363,6 +1027,11 @@ impl<T, const N: usize> ChannelState<T, N> {
 /// received from the channel.
 ///
 /// All data sent will become available in the same order as it was sent.
+///
+/// The channel is closed when [`close`](Channel::close) is called, or when the last
+/// [`CountedSender`] or the last [`CountedReceiver`] is dropped. A closed channel stays closed.
+/// Sending to a closed channel fails, and receiving from it fails once all messages sent before
+/// it was closed have been received.
 pub struct Channel<M, T, const N: usize>
 where
     M: RawMutex,
@@ -401,6 +1070,33 @@ where
         self.lock(|c| c.try_send_with_context(m, cx))
     }
 
+    fn try_recv_many_with_context(&self, buf: &mut [T], cx: Option<&mut Context<'_>>) -> Result<usize, TryRecvError> {
+        self.lock(|c| c.try_recv_many_with_context(buf, cx))
+    }
+
+    fn try_peek_with_context(&self, cx: Option<&mut Context<'_>>) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        self.lock(|c| c.try_peek_with_context(cx))
+    }
+
+    fn register_sender(&self) {
+        self.lock(|c| c.handles.register_sender())
+    }
+
+    fn unregister_sender(&self) {
+        self.lock(|c| c.unregister_sender())
+    }
+
+    fn register_receiver(&self) {
+        self.lock(|c| c.handles.register_receiver())
+    }
+
+    fn unregister_receiver(&self) {
+        self.lock(|c| c.unregister_receiver())
+    }
+
     /// Get a sender for this channel.
     pub fn sender(&self) -> Sender<'_, M, T, N> {
         Sender { channel: self }
@@ -411,10 +1107,30 @@ where
         Receiver { channel: self }
     }
 
+    /// Get a counted sender for this channel.
+    ///
+    /// The channel is closed once all counted senders have been dropped.
+    pub fn counted_sender(&self) -> CountedSender<'_, M, T, N> {
+        self.register_sender();
+        CountedSender { channel: self }
+    }
+
+    /// Get a counted receiver for this channel.
+    ///
+    /// The channel is closed once all counted receivers have been dropped.
+    pub fn counted_receiver(&self) -> CountedReceiver<'_, M, T, N> {
+        self.register_receiver();
+        CountedReceiver { channel: self }
+    }
+
     /// Send a value, waiting until there is capacity.
     ///
     /// Sending completes when the value has been pushed to the channel's queue.
     /// This doesn't mean the value has been received yet.
+    ///
+    /// # Errors
+    ///
+    /// If the channel is closed, the message is returned in a [`SendError`].
     pub fn send(&self, message: T) -> SendFuture<'_, M, T, N> {
         SendFuture {
             channel: self,
@@ -431,7 +1147,7 @@ where
     ///
     /// If the channel capacity has been reached, i.e., the channel has `n`
     /// buffered values where `n` is the argument passed to [`Channel`], then an
-    /// error is returned.
+    /// error is returned. An error is also returned if the channel is closed.
     pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
         self.lock(|c| c.try_send(message))
     }
@@ -440,6 +1156,11 @@ where
     ///
     /// If there are no messages in the channel's buffer, this method will
     /// wait until a message is sent.
+    ///
+    /// # Errors
+    ///
+    /// If the channel is closed and there are no messages left in its buffer,
+    /// a [`RecvError`] is returned.
     pub fn recv(&self) -> RecvFuture<'_, M, T, N> {
         RecvFuture { channel: self }
     }
@@ -451,6 +1172,113 @@ where
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         self.lock(|c| c.try_recv())
     }
+
+    /// Send all values of an iterator, waiting for capacity as needed.
+    ///
+    /// Messages are pushed as long as there is capacity, so receivers can pick them up in batches.
+    /// The iterator is advanced with the channel unlocked, so it may take time or use the channel
+    /// itself. Completes once the iterator is exhausted.
+    ///
+    /// # Errors
+    ///
+    /// If the channel is closed, the first message that could not be sent is returned in a
+    /// [`SendError`]. Messages sent before the channel was closed stay in the channel, the
+    /// remaining messages of the iterator are not consumed.
+    pub async fn send_all(&self, messages: impl IntoIterator<Item = T>) -> Result<(), SendError<T>> {
+        let mut messages = messages.into_iter();
+        let mut pending = None;
+        poll_fn(|cx| poll_send_all(self, &mut pending, &mut messages, cx)).await
+    }
+
+    /// Receive multiple values, waiting until at least one is available.
+    ///
+    /// Moves as many messages as are available, up to the length of `buf`, to the start of `buf`
+    /// and returns how many were received. Returns `Ok(0)` immediately if `buf` is empty.
+    ///
+    /// # Errors
+    ///
+    /// If the channel is closed and there are no messages left in its buffer,
+    /// a [`RecvError`] is returned.
+    pub async fn recv_many(&self, buf: &mut [T]) -> Result<usize, RecvError> {
+        poll_fn(|cx| poll_recv(self.try_recv_many_with_context(buf, Some(cx)))).await
+    }
+
+    /// Attempt to immediately receive multiple values.
+    ///
+    /// Like [`recv_many`](Channel::recv_many), but returns [`TryRecvError::Empty`] instead of
+    /// waiting if the channel is empty.
+    pub fn try_recv_many(&self, buf: &mut [T]) -> Result<usize, TryRecvError> {
+        self.try_recv_many_with_context(buf, None)
+    }
+
+    /// Wait for the next value without removing it from the channel.
+    ///
+    /// Returns a clone of the message the next call to [`recv`](Channel::recv) would return,
+    /// unless another receiver takes it first.
+    ///
+    /// # Errors
+    ///
+    /// If the channel is closed and there are no messages left in its buffer,
+    /// a [`RecvError`] is returned.
+    pub async fn peek(&self) -> Result<T, RecvError>
+    where
+        T: Clone,
+    {
+        poll_fn(|cx| poll_recv(self.try_peek_with_context(Some(cx)))).await
+    }
+
+    /// Attempt to immediately get the next value without removing it from the channel.
+    pub fn try_peek(&self) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        self.try_peek_with_context(None)
+    }
+
+    /// Returns the number of messages in the channel.
+    pub fn len(&self) -> usize {
+        self.lock(|c| c.queue.len())
+    }
+
+    /// Returns whether the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.lock(|c| c.queue.is_empty())
+    }
+
+    /// Returns whether the channel is full.
+    pub fn is_full(&self) -> bool {
+        self.lock(|c| c.queue.is_full())
+    }
+
+    /// Returns the maximum number of messages the channel can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of messages that can be sent before the channel is full.
+    pub fn free_capacity(&self) -> usize {
+        N - self.len()
+    }
+
+    /// Remove all messages from the channel.
+    ///
+    /// Senders waiting for capacity are woken up. This does not close the channel.
+    pub fn clear(&self) {
+        self.lock(|c| c.clear())
+    }
+
+    /// Close the channel.
+    ///
+    /// Pending and future sends fail. Messages already in the channel can still be received,
+    /// after which receiving fails as well. Waiting senders and receivers are woken up.
+    pub fn close(&self) {
+        self.lock(|c| c.close())
+    }
+
+    /// Returns whether the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.lock(|c| c.handles.is_closed())
+    }
 }
 
 /// Implements the DynamicChannel to allow creating types that are unaware of the queue size with the
@@ -466,6 +1294,37 @@ where
     fn try_recv_with_context(&self, cx: Option<&mut Context<'_>>) -> Result<T, TryRecvError> {
         Channel::try_recv_with_context(self, cx)
     }
+
+    fn try_recv_many_with_context(&self, buf: &mut [T], cx: Option<&mut Context<'_>>) -> Result<usize, TryRecvError> {
+        Channel::try_recv_many_with_context(self, buf, cx)
+    }
+
+    fn try_peek_with_context(&self, cx: Option<&mut Context<'_>>) -> Result<T, TryRecvError>
+    where
+        T: Clone,
+    {
+        Channel::try_peek_with_context(self, cx)
+    }
+
+    fn len(&self) -> usize {
+        Channel::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        Channel::capacity(self)
+    }
+
+    fn clear(&self) {
+        Channel::clear(self)
+    }
+
+    fn close(&self) {
+        Channel::close(self)
+    }
+
+    fn is_closed(&self) -> bool {
+        Channel::is_closed(self)
+    }
 }
 
 #[cfg(test)]
@@ -561,14 +1420,14 @@ mod tests {
                 assert!(c2.try_send(1).is_ok());
             })
             .is_ok());
-        assert_eq!(c.recv().await, 1);
+        assert_eq!(c.recv().await, Ok(1));
     }
 
     #[futures_test::test]
     async fn sender_send_completes_if_capacity() {
         let c = Channel::<CriticalSectionRawMutex, u32, 1>::new();
-        c.send(1).await;
-        assert_eq!(c.recv().await, 1);
+        assert_eq!(c.send(1).await, Ok(()));
+        assert_eq!(c.recv().await, Ok(1));
     }
 
     #[futures_test::test]
@@ -586,15 +1445,227 @@ mod tests {
         // Wish I could think of a means of determining that the async send is waiting instead.
         // However, I've used the debugger to observe that the send does indeed wait.
         Delay::new(Duration::from_millis(500)).await;
-        assert_eq!(c.recv().await, 1);
+        assert_eq!(c.recv().await, Ok(1));
         assert!(executor
             .spawn(async move {
-                loop {
-                    c.recv().await;
-                }
+                while c.recv().await.is_ok() {}
+            })
+            .is_ok());
+        assert_eq!(send_task_1.unwrap().await, Ok(()));
+        assert_eq!(send_task_2.unwrap().await, Ok(()));
+    }
+
+    #[test]
+    fn closing() {
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        assert!(c.try_send(1).is_ok());
+        assert!(!c.is_closed());
+
+        c.close();
+        assert!(c.is_closed());
+        assert_eq!(c.try_send(2), Err(TrySendError::Closed(2)));
+
+        // Messages sent before closing can still be received.
+        assert_eq!(c.try_recv(), Ok(1));
+        assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn plain_handles_do_not_close() {
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        let _ = c.sender();
+        let _ = c.receiver();
+        let _: DynamicSender<'_, u32> = c.sender().into();
+        let _: DynamicReceiver<'_, u32> = c.receiver().into();
+        assert!(!c.is_closed());
+        assert!(c.try_send(1).is_ok());
+    }
+
+    #[test]
+    fn closed_when_last_counted_sender_dropped() {
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        let r = c.counted_receiver();
+        let s1 = c.counted_sender();
+        let s2 = s1.clone();
+
+        assert!(s1.try_send(1).is_ok());
+        drop(s1);
+        assert!(!r.is_closed());
+        drop(s2);
+        assert!(r.is_closed());
+
+        assert_eq!(r.try_recv(), Ok(1));
+        assert_eq!(r.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn closed_when_last_counted_receiver_dropped() {
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        let s = c.counted_sender();
+        let r1 = c.counted_receiver();
+        let r2 = r1.clone();
+
+        drop(r1);
+        assert!(s.try_send(1).is_ok());
+        drop(r2);
+        assert_eq!(s.try_send(2), Err(TrySendError::Closed(2)));
+    }
+
+    #[futures_test::test]
+    async fn receiver_woken_when_senders_dropped() {
+        let executor = ThreadPool::new().unwrap();
+
+        static CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, u32, 3>> = StaticCell::new();
+        let c = &*CHANNEL.init(Channel::new());
+        let r = c.counted_receiver();
+        let s = c.counted_sender();
+
+        assert!(executor
+            .spawn(async move {
+                Delay::new(Duration::from_millis(100)).await;
+                s.send(1).await.unwrap();
+            })
+            .is_ok());
+
+        assert_eq!(r.recv().await, Ok(1));
+        assert_eq!(r.recv().await, Err(RecvError));
+    }
+
+    #[futures_test::test]
+    async fn sender_woken_when_closed() {
+        let executor = ThreadPool::new().unwrap();
+
+        static CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, u32, 1>> = StaticCell::new();
+        let c = &*CHANNEL.init(Channel::new());
+        let s = c.counted_sender();
+        assert!(s.try_send(1).is_ok());
+
+        assert!(executor
+            .spawn(async move {
+                Delay::new(Duration::from_millis(100)).await;
+                c.close();
             })
             .is_ok());
-        send_task_1.unwrap().await;
-        send_task_2.unwrap().await;
+
+        assert_eq!(s.send(2).await, Err(SendError(2)));
+    }
+
+    #[futures_test::test]
+    async fn plain_handles_woken_when_closed() {
+        let executor = ThreadPool::new().unwrap();
+
+        static CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, u32, 1>> = StaticCell::new();
+        let c = &*CHANNEL.init(Channel::new());
+        let r = c.receiver();
+
+        let recv_task = executor.spawn_with_handle(async move { r.recv().await }).unwrap();
+        Delay::new(Duration::from_millis(100)).await;
+        c.close();
+        assert_eq!(recv_task.await, Err(RecvError));
+
+        let r: DynamicReceiver<'_, u32> = c.receiver().into();
+        assert_eq!(r.recv().await, Err(RecvError));
+        let s: DynamicSender<'_, u32> = c.sender().into();
+        assert_eq!(s.send(1).await, Err(SendError(1)));
+        assert_eq!(c.sender().send(2).await, Err(SendError(2)));
+    }
+
+    #[test]
+    fn receiving_many() {
+        let c = Channel::<NoopRawMutex, u32, 4>::new();
+        let mut buf = [0; 3];
+        assert_eq!(c.try_recv_many(&mut buf), Err(TryRecvError::Empty));
+
+        for i in 1..=4 {
+            assert!(c.try_send(i).is_ok());
+        }
+        assert_eq!(c.try_recv_many(&mut buf), Ok(3));
+        assert_eq!(buf, [1, 2, 3]);
+        assert_eq!(c.try_recv_many(&mut buf), Ok(1));
+        assert_eq!(buf[0], 4);
+        assert_eq!(c.try_recv_many(&mut []), Ok(0));
+
+        c.close();
+        assert_eq!(c.try_recv_many(&mut buf), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn peeking() {
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        assert_eq!(c.try_peek(), Err(TryRecvError::Empty));
+        assert!(c.try_send(1).is_ok());
+        assert!(c.try_send(2).is_ok());
+        assert_eq!(c.try_peek(), Ok(1));
+        assert_eq!(c.try_peek(), Ok(1));
+        assert_eq!(c.try_recv(), Ok(1));
+        assert_eq!(c.try_peek(), Ok(2));
+    }
+
+    #[test]
+    fn len_and_clear() {
+        let c = Channel::<NoopRawMutex, u32, 2>::new();
+        let s = c.sender();
+        let r: DynamicReceiver<'_, u32> = c.receiver().into();
+        assert!(r.is_empty());
+        assert_eq!(s.free_capacity(), 2);
+
+        assert!(s.try_send(1).is_ok());
+        assert_eq!(r.len(), 1);
+        assert!(s.try_send(2).is_ok());
+        assert!(c.is_full());
+        assert_eq!(r.free_capacity(), 0);
+
+        r.clear();
+        assert!(c.is_empty());
+        assert_eq!(s.free_capacity(), 2);
+        assert!(!c.is_closed());
+    }
+
+    #[futures_test::test]
+    async fn send_all_waits_for_capacity() {
+        let executor = ThreadPool::new().unwrap();
+
+        static CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, u32, 2>> = StaticCell::new();
+        let c = &*CHANNEL.init(Channel::new());
+        let s = c.counted_sender();
+        let r: DynamicReceiver<'_, u32> = c.receiver().into();
+
+        let send_task = executor
+            .spawn_with_handle(async move { s.send_all(0..5).await })
+            .unwrap();
+
+        let mut buf = [0; 4];
+        let mut received = 0;
+        while received < 5 {
+            let n = r.recv_many(&mut buf).await.unwrap();
+            assert!(n <= 2);
+            for (i, v) in buf[..n].iter().enumerate() {
+                assert_eq!(*v, (received + i) as u32);
+            }
+            received += n;
+        }
+        assert_eq!(send_task.await, Ok(()));
+        assert_eq!(r.recv_many(&mut buf).await, Err(RecvError));
+    }
+
+    #[futures_test::test]
+    async fn send_all_iterates_outside_lock() {
+        let c = Channel::<NoopRawMutex, usize, 3>::new();
+        // The iterator inspects the channel, which would panic if it ran with the channel locked.
+        let messages = core::iter::repeat(()).map(|_| c.len()).take(3);
+        assert_eq!(c.send_all(messages).await, Ok(()));
+        assert_eq!(c.try_recv(), Ok(0));
+        assert_eq!(c.try_recv(), Ok(1));
+        assert_eq!(c.try_recv(), Ok(2));
+    }
+
+    #[futures_test::test]
+    async fn send_all_into_closed_channel() {
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        assert_eq!(c.send_all([1, 2]).await, Ok(()));
+        c.close();
+        assert_eq!(c.send_all([3, 4]).await, Err(SendError(3)));
+        assert_eq!(c.peek().await, Ok(1));
+        assert_eq!(c.len(), 2);
     }
 }
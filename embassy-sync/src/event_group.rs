@@ -0,0 +1,170 @@
+//! A synchronization primitive for waiting on a group of event flags.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A group of 32 event flags that tasks can wait on, similar to event flags in an RTOS.
+///
+/// Flags are set with [`set`](EventGroup::set) and stay set until they are cleared with
+/// [`clear`](EventGroup::clear). Tasks can wait for any or all flags of a mask to be set.
+///
+/// Unlike a [`Signal`](crate::signal::Signal), any number of tasks can wait at the same time. `N`
+/// is the amount of wakers that can be registered at once: when more tasks wait, the waiting tasks
+/// are woken up more often than necessary, but no wakeup is lost.
+///
+/// Waiters only see the flags at the time they are polled. If flags are set and cleared again
+/// before a waiting task gets to run, the task keeps waiting.
+///
+/// ## Example
+///
+/// ```
+/// # use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// # use embassy_sync::event_group::EventGroup;
+/// # use futures_executor::block_on;
+/// # let test = async {
+/// const RX_DONE: u32 = 1 << 0;
+/// const TX_DONE: u32 = 1 << 1;
+///
+/// let events = EventGroup::<NoopRawMutex, 4>::new();
+///
+/// events.set(RX_DONE);
+/// assert_eq!(events.wait_any(RX_DONE | TX_DONE).await, RX_DONE);
+///
+/// events.set(TX_DONE);
+/// assert_eq!(events.wait_all(RX_DONE | TX_DONE).await, RX_DONE | TX_DONE);
+///
+/// events.clear(RX_DONE);
+/// assert_eq!(events.get(), TX_DONE);
+/// # };
+/// #
+/// # block_on(test);
+/// ```
+pub struct EventGroup<M: RawMutex, const N: usize> {
+    inner: Mutex<M, RefCell<State<N>>>,
+}
+
+struct State<const N: usize> {
+    flags: u32,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<M: RawMutex, const N: usize> EventGroup<M, N> {
+    /// Create a new `EventGroup` with all flags cleared.
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(State {
+                flags: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Set the flags in `mask`, waking the waiting tasks. Returns the flags as they were before.
+    pub fn set(&self, mask: u32) -> u32 {
+        self.inner.lock(|s| {
+            let mut s = s.borrow_mut();
+            let flags = s.flags;
+            s.flags |= mask;
+            if s.flags != flags {
+                s.wakers.wake();
+            }
+            flags
+        })
+    }
+
+    /// Clear the flags in `mask`. Returns the flags as they were before.
+    pub fn clear(&self, mask: u32) -> u32 {
+        self.inner.lock(|s| {
+            let mut s = s.borrow_mut();
+            let flags = s.flags;
+            s.flags &= !mask;
+            flags
+        })
+    }
+
+    /// Get the flags that are currently set.
+    pub fn get(&self) -> u32 {
+        self.inner.lock(|s| s.borrow().flags)
+    }
+
+    /// Wait until any of the flags in `mask` is set.
+    ///
+    /// Returns the flags of `mask` that are set. If `mask` is empty, this waits forever.
+    pub async fn wait_any(&self, mask: u32) -> u32 {
+        self.wait(|flags| flags & mask != 0, mask).await
+    }
+
+    /// Wait until all of the flags in `mask` are set.
+    ///
+    /// Returns the flags of `mask` that are set, which is `mask` itself. If `mask` is empty, this
+    /// returns immediately.
+    pub async fn wait_all(&self, mask: u32) -> u32 {
+        self.wait(|flags| flags & mask == mask, mask).await
+    }
+
+    async fn wait(&self, done: impl Fn(u32) -> bool, mask: u32) -> u32 {
+        poll_fn(|cx| {
+            self.inner.lock(|s| {
+                let mut s = s.borrow_mut();
+                if done(s.flags) {
+                    Poll::Ready(s.flags & mask)
+                } else {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+
+    use super::*;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    #[futures_test::test]
+    async fn set_and_clear() {
+        let events = EventGroup::<NoopRawMutex, 1>::new();
+
+        assert_eq!(events.set(0b0101), 0);
+        assert_eq!(events.set(0b0011), 0b0101);
+        assert_eq!(events.clear(0b0110), 0b0111);
+        assert_eq!(events.get(), 0b0001);
+
+        assert_eq!(events.wait_any(0b1001).await, 0b0001);
+        assert_eq!(events.wait_all(0).await, 0);
+    }
+
+    #[futures_test::test]
+    async fn wakes_all_waiters() {
+        let executor = ThreadPool::new().unwrap();
+
+        static EVENTS: EventGroup<CriticalSectionRawMutex, 2> = EventGroup::new();
+
+        // More waiters than waker slots
+        let any = executor.spawn_with_handle(EVENTS.wait_any(0b011)).unwrap();
+        let all = executor.spawn_with_handle(EVENTS.wait_all(0b011)).unwrap();
+        let other = executor.spawn_with_handle(EVENTS.wait_any(0b010)).unwrap();
+
+        Delay::new(Duration::from_millis(100)).await;
+        EVENTS.set(0b001);
+        assert_eq!(any.await, 0b001);
+
+        Delay::new(Duration::from_millis(100)).await;
+        EVENTS.set(0b110);
+        assert_eq!(all.await, 0b011);
+        assert_eq!(other.await, 0b010);
+    }
+}
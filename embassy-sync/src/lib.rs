@@ -11,10 +11,21 @@ pub(crate) mod fmt;
 // internal use
 mod ring_buffer;
 
+pub mod barrier;
 pub mod blocking_mutex;
 pub mod channel;
+pub mod event_group;
 pub mod mutex;
+pub mod once_lock;
 pub mod pipe;
+pub mod pool;
+pub mod priority_channel;
 pub mod pubsub;
+pub mod rwlock;
+pub mod semaphore;
 pub mod signal;
+pub mod spsc;
+pub mod wait_group;
 pub mod waitqueue;
+pub mod watch;
+pub mod zerocopy_channel;
@@ -2,10 +2,14 @@
 //!
 //! This module provides a mutex that can be used to synchronize data between asynchronous tasks.
 use core::cell::{RefCell, UnsafeCell};
-use core::future::poll_fn;
+use core::future::{poll_fn, Future};
 use core::ops::{Deref, DerefMut};
+use core::panic::Location;
 use core::task::Poll;
 
+#[cfg(feature = "time")]
+use embassy_time::{with_timeout, Duration, TimeoutError};
+
 use crate::blocking_mutex::raw::RawMutex;
 use crate::blocking_mutex::Mutex as BlockingMutex;
 use crate::waitqueue::WakerRegistration;
@@ -18,6 +22,28 @@ pub struct TryLockError;
 struct State {
     locked: bool,
     waker: WakerRegistration,
+    contention: u32,
+    #[cfg(feature = "mutex-owner")]
+    owner: Option<&'static Location<'static>>,
+}
+
+impl State {
+    fn try_lock(&mut self, _caller: &'static Location<'static>) -> bool {
+        if self.locked {
+            return false;
+        }
+
+        self.locked = true;
+        #[cfg(feature = "mutex-owner")]
+        {
+            self.owner = Some(_caller);
+        }
+        true
+    }
+
+    fn count_contention(&mut self) {
+        self.contention = self.contention.wrapping_add(1);
+    }
 }
 
 /// Async mutex.
@@ -35,6 +61,12 @@ struct State {
 ///
 /// Use [`ThreadModeRawMutex`](crate::blocking_mutex::raw::ThreadModeRawMutex) when data is shared between tasks running on the same executor but you want a singleton.
 ///
+/// # Diagnostics
+///
+/// To help tracking down deadlocks and lock convoys, the mutex counts how often a task found it
+/// locked, see [`contention_count`](Mutex::contention_count). With the `mutex-owner` feature, it
+/// also records where it was locked, see `Mutex::owner`. With the `time` feature,
+/// `Mutex::lock_timeout` gives up waiting after a timeout.
 pub struct Mutex<M, T>
 where
     M: RawMutex,
@@ -59,6 +91,9 @@ where
             state: BlockingMutex::new(RefCell::new(State {
                 locked: false,
                 waker: WakerRegistration::new(),
+                contention: 0,
+                #[cfg(feature = "mutex-owner")]
+                owner: None,
             })),
         }
     }
@@ -72,16 +107,23 @@ where
     /// Lock the mutex.
     ///
     /// This will wait for the mutex to be unlocked if it's already locked.
-    pub async fn lock(&self) -> MutexGuard<'_, M, T> {
-        poll_fn(|cx| {
+    #[track_caller]
+    pub fn lock(&self) -> impl Future<Output = MutexGuard<'_, M, T>> {
+        let caller = Location::caller();
+        let mut contended = false;
+        poll_fn(move |cx| {
             let ready = self.state.lock(|s| {
                 let mut s = s.borrow_mut();
-                if s.locked {
+                if s.try_lock(caller) {
+                    true
+                } else {
+                    // Count each waiting `lock` call once, no matter how often it is polled.
+                    if !contended {
+                        contended = true;
+                        s.count_contention();
+                    }
                     s.waker.register(cx.waker());
                     false
-                } else {
-                    s.locked = true;
-                    true
                 }
             });
 
@@ -91,26 +133,62 @@ where
                 Poll::Pending
             }
         })
-        .await
+    }
+
+    /// Lock the mutex, giving up if it could not be locked within `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TimeoutError`] if the mutex is still locked when the timeout expires.
+    #[cfg(feature = "time")]
+    #[track_caller]
+    pub fn lock_timeout(&self, timeout: Duration) -> impl Future<Output = Result<MutexGuard<'_, M, T>, TimeoutError>> {
+        let lock = self.lock();
+        with_timeout(timeout, lock)
     }
 
     /// Attempt to immediately lock the mutex.
     ///
     /// If the mutex is already locked, this will return an error instead of waiting.
+    #[track_caller]
     pub fn try_lock(&self) -> Result<MutexGuard<'_, M, T>, TryLockError> {
+        let caller = Location::caller();
         self.state.lock(|s| {
             let mut s = s.borrow_mut();
-            if s.locked {
-                Err(TryLockError)
-            } else {
-                s.locked = true;
+            if s.try_lock(caller) {
                 Ok(())
+            } else {
+                s.count_contention();
+                Err(TryLockError)
             }
         })?;
 
         Ok(MutexGuard { mutex: self })
     }
 
+    /// Returns how often the mutex was found locked by [`lock`](Mutex::lock) or
+    /// [`try_lock`](Mutex::try_lock).
+    ///
+    /// A `lock` call that has to wait is counted once, however long it waits. The counter
+    /// wraps around on overflow.
+    pub fn contention_count(&self) -> u32 {
+        self.state.lock(|s| s.borrow().contention)
+    }
+
+    /// Reset the counter returned by [`contention_count`](Mutex::contention_count) to zero.
+    pub fn reset_contention_count(&self) {
+        self.state.lock(|s| s.borrow_mut().contention = 0)
+    }
+
+    /// Returns where the mutex was locked, if it is currently locked.
+    ///
+    /// This is the location of the `lock`, `lock_timeout` or `try_lock` call that returned the
+    /// live guard. Only available with the `mutex-owner` feature.
+    #[cfg(feature = "mutex-owner")]
+    pub fn owner(&self) -> Option<&'static Location<'static>> {
+        self.state.lock(|s| s.borrow().owner)
+    }
+
     /// Consumes this mutex, returning the underlying data.
     pub fn into_inner(self) -> T
     where
@@ -151,6 +229,10 @@ where
         self.mutex.state.lock(|s| {
             let mut s = s.borrow_mut();
             s.locked = false;
+            #[cfg(feature = "mutex-owner")]
+            {
+                s.owner = None;
+            }
             s.waker.wake();
         })
     }
@@ -180,3 +262,86 @@ where
         unsafe { &mut *(self.mutex.inner.get()) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future::poll_fn;
+    use futures_util::pin_mut;
+
+    use super::*;
+    use crate::blocking_mutex::raw::NoopRawMutex;
+
+    #[futures_test::test]
+    async fn counts_contention() {
+        let mutex = Mutex::<NoopRawMutex, u32>::new(0);
+        let guard = mutex.lock().await;
+        assert_eq!(mutex.contention_count(), 0);
+
+        assert!(mutex.try_lock().is_err());
+        assert_eq!(mutex.contention_count(), 1);
+
+        let waiting = mutex.lock();
+        pin_mut!(waiting);
+        // Polling a waiting `lock` repeatedly counts only once.
+        for _ in 0..3 {
+            poll_fn(|cx| {
+                assert!(waiting.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+        }
+        assert_eq!(mutex.contention_count(), 2);
+
+        drop(guard);
+        let _guard = waiting.await;
+        assert_eq!(mutex.contention_count(), 2);
+
+        mutex.reset_contention_count();
+        assert_eq!(mutex.contention_count(), 0);
+    }
+
+    #[cfg(feature = "time")]
+    #[futures_test::test]
+    async fn lock_timeout() {
+        use core::task::Context;
+
+        use embassy_time::MockDriver;
+        use futures_util::task::noop_waker_ref;
+
+        let driver = MockDriver::get();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let mutex = Mutex::<NoopRawMutex, u32>::new(0);
+        let guard = mutex.lock().await;
+
+        let waiting = mutex.lock_timeout(Duration::from_millis(10));
+        pin_mut!(waiting);
+        assert!(waiting.as_mut().poll(cx).is_pending());
+        driver.advance(Duration::from_millis(9));
+        assert!(waiting.as_mut().poll(cx).is_pending());
+        driver.advance(Duration::from_millis(1));
+        assert!(matches!(waiting.as_mut().poll(cx), Poll::Ready(Err(TimeoutError))));
+
+        // The mutex is handed over if it is unlocked before the timeout.
+        let waiting = mutex.lock_timeout(Duration::from_millis(10));
+        pin_mut!(waiting);
+        assert!(waiting.as_mut().poll(cx).is_pending());
+        driver.advance(Duration::from_millis(5));
+        drop(guard);
+        assert!(matches!(waiting.as_mut().poll(cx), Poll::Ready(Ok(_))));
+    }
+
+    #[cfg(feature = "mutex-owner")]
+    #[futures_test::test]
+    async fn tracks_owner() {
+        let mutex = Mutex::<NoopRawMutex, u32>::new(0);
+        assert!(mutex.owner().is_none());
+
+        let guard = mutex.lock().await;
+        let owner = mutex.owner().unwrap();
+        assert_eq!(owner.file(), file!());
+        assert_eq!(owner.line(), line!() - 3);
+
+        drop(guard);
+        assert!(mutex.owner().is_none());
+    }
+}
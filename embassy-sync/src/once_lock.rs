@@ -0,0 +1,343 @@
+//! Synchronization primitive for initializing a value once, allowing others to await a reference to the value.
+
+use core::cell::{RefCell, UnsafeCell};
+use core::future::{poll_fn, Future};
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+use crate::blocking_mutex::raw::CriticalSectionRawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::{WakerList, WakerListEntry};
+
+/// The `OnceLock` is a synchronization primitive that allows for
+/// initializing a value once, and allowing others to `.await` a
+/// reference to the value. This is useful for lazy initialization of
+/// a static value produced by another task, such as a network stack or a
+/// calibrated driver.
+///
+/// **Note**: this implementation uses a critical section, so it can be initialized
+/// from an interrupt handler. Reading an initialized value does not take the critical section.
+///
+/// # Example
+/// ```
+/// use futures_executor::block_on;
+/// use embassy_sync::once_lock::OnceLock;
+///
+/// // Define a static value that will be lazily initialized
+/// static VALUE: OnceLock<u32> = OnceLock::new();
+///
+/// let f = async {
+///
+/// // Initialize the value
+/// let reference = VALUE.get_or_init(|| async { 20 }).await;
+/// assert_eq!(reference, &20);
+///
+/// // Wait for the value to be initialized
+/// // and get a static reference it
+/// assert_eq!(VALUE.get().await, &20);
+///
+/// };
+/// block_on(f)
+/// ```
+pub struct OnceLock<T> {
+    init: AtomicBool,
+    data: UnsafeCell<MaybeUninit<T>>,
+    state: Mutex<CriticalSectionRawMutex, RefCell<State>>,
+}
+
+struct State {
+    /// A task is running the initializer passed to [`OnceLock::get_or_init`].
+    initializing: bool,
+    wakers: WakerList,
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+impl<T> OnceLock<T> {
+    /// Create a new uninitialized `OnceLock`.
+    pub const fn new() -> Self {
+        Self {
+            init: AtomicBool::new(false),
+            data: UnsafeCell::new(MaybeUninit::uninit()),
+            state: Mutex::new(RefCell::new(State {
+                initializing: false,
+                wakers: WakerList::new(),
+            })),
+        }
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        self.state.lock(|s| f(&mut s.borrow_mut()))
+    }
+
+    /// Get a reference to the underlying value, waiting for it to be set.
+    ///
+    /// If the value is already set, this returns immediately.
+    pub async fn get(&self) -> &T {
+        let mut waiter = Waiter::new(self);
+
+        poll_fn(|cx| {
+            if let Some(data) = self.try_get() {
+                return Poll::Ready(data);
+            }
+
+            self.lock(|s| match self.try_get() {
+                // The value was set just before taking the lock.
+                Some(data) => Poll::Ready(data),
+                None => {
+                    // Safety: `waiter` stays in place in this future until it is dropped.
+                    unsafe { s.wakers.register(&waiter.entry, cx.waker()) };
+                    waiter.registered = true;
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    /// Try to get a reference to the underlying value if it exists.
+    pub fn try_get(&self) -> Option<&T> {
+        if self.init.load(Ordering::Acquire) {
+            // Safety: `init` is only set after the data has been written, and the data is never
+            // modified afterwards.
+            Some(unsafe { (*self.data.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Set the underlying value. If the value is already set, this returns the given value
+    /// back as an error.
+    ///
+    /// Tasks waiting in [`get`](OnceLock::get) or [`get_or_init`](OnceLock::get_or_init) are
+    /// woken up.
+    pub fn init(&self, value: T) -> Result<(), T> {
+        self.lock(|s| {
+            if self.init.load(Ordering::Relaxed) {
+                return Err(value);
+            }
+
+            // Safety: the value is not set yet, so there are no references to the data, and
+            // concurrent `init` calls are serialized by the lock.
+            unsafe { (*self.data.get()).write(value) };
+            self.init.store(true, Ordering::Release);
+            s.wakers.wake();
+            Ok(())
+        })
+    }
+
+    /// Get a reference to the underlying value, initializing it with the future returned
+    /// by `f` if it is not set yet.
+    ///
+    /// Only one initializer runs at a time: if another task is already initializing the
+    /// value, this waits for it instead of calling `f`. If that initialization is cancelled,
+    /// one of the waiting tasks runs its own initializer.
+    ///
+    /// If the value is set with [`init`](OnceLock::init) while `f` is running, that value is
+    /// kept and the one produced by `f` is dropped.
+    pub async fn get_or_init<F, Fut>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let mut waiter = Waiter::new(self);
+        let claimed = poll_fn(|cx| {
+            self.lock(|s| {
+                if self.init.load(Ordering::Relaxed) {
+                    Poll::Ready(false)
+                } else if s.initializing {
+                    // Safety: `waiter` stays in place in this future until it is dropped.
+                    unsafe { s.wakers.register(&waiter.entry, cx.waker()) };
+                    waiter.registered = true;
+                    Poll::Pending
+                } else {
+                    s.initializing = true;
+                    Poll::Ready(true)
+                }
+            })
+        })
+        .await;
+        drop(waiter);
+
+        if claimed {
+            // Let another task take over if this future is dropped while initializing.
+            let _guard = Initializing { lock: self };
+            let value = f().await;
+            // The value might have been set with `init` in the meantime, which is fine.
+            let _ = self.init(value);
+        }
+
+        unwrap!(self.try_get())
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if *self.init.get_mut() {
+            // Safety: the value is initialized.
+            unsafe { self.data.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// A task waiting in [`OnceLock::get`] or [`OnceLock::get_or_init`]. It leaves the list of waiting
+/// tasks when dropped.
+struct Waiter<'a, T> {
+    lock: &'a OnceLock<T>,
+    entry: WakerListEntry,
+    /// Whether the entry was ever registered. If not, dropping doesn't need the lock.
+    registered: bool,
+}
+
+impl<'a, T> Waiter<'a, T> {
+    fn new(lock: &'a OnceLock<T>) -> Self {
+        Self {
+            lock,
+            entry: WakerListEntry::new(),
+            registered: false,
+        }
+    }
+}
+
+impl<'a, T> Drop for Waiter<'a, T> {
+    fn drop(&mut self) {
+        if self.registered {
+            self.lock.lock(|s| s.wakers.remove(&self.entry))
+        }
+    }
+}
+
+/// Resets the initializing state of a [`OnceLock`] when `get_or_init` completes or is cancelled.
+struct Initializing<'a, T> {
+    lock: &'a OnceLock<T>,
+}
+
+impl<'a, T> Drop for Initializing<'a, T> {
+    fn drop(&mut self) {
+        self.lock.lock(|s| {
+            s.initializing = false;
+            s.wakers.wake();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::pin::pin;
+    use core::task::Context;
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+
+    use super::*;
+
+    #[test]
+    fn once_lock() {
+        let lock = OnceLock::<_>::new();
+        assert_eq!(lock.try_get(), None);
+        assert_eq!(lock.init(1), Ok(()));
+        assert_eq!(lock.try_get(), Some(&1));
+        assert_eq!(lock.init(2), Err(2));
+        assert_eq!(lock.try_get(), Some(&1));
+    }
+
+    #[futures_test::test]
+    async fn get_waits_for_init() {
+        let executor = ThreadPool::new().unwrap();
+
+        static LOCK: OnceLock<u32> = OnceLock::new();
+        executor
+            .spawn(async {
+                Delay::new(Duration::from_millis(100)).await;
+                LOCK.init(42).unwrap();
+            })
+            .unwrap();
+
+        assert_eq!(LOCK.get().await, &42);
+    }
+
+    #[test]
+    fn init_wakes_all_waiters() {
+        let lock = OnceLock::<u32>::new();
+        let (waker1, count1) = futures_test::task::new_count_waker();
+        let (waker2, count2) = futures_test::task::new_count_waker();
+
+        let mut get1 = pin!(lock.get());
+        let mut get2 = pin!(lock.get());
+        assert!(get1.as_mut().poll(&mut Context::from_waker(&waker1)).is_pending());
+        assert!(get2.as_mut().poll(&mut Context::from_waker(&waker2)).is_pending());
+        // A waiter that stops waiting leaves the waiter list.
+        let (waker3, count3) = futures_test::task::new_count_waker();
+        {
+            let mut get3 = pin!(lock.get());
+            assert!(get3.as_mut().poll(&mut Context::from_waker(&waker3)).is_pending());
+        }
+        // Polling again does not wake the other waiter.
+        assert!(get1.as_mut().poll(&mut Context::from_waker(&waker1)).is_pending());
+        assert_eq!(count1, 0);
+        assert_eq!(count2, 0);
+
+        lock.init(7).unwrap();
+        assert_eq!(count1, 1);
+        assert_eq!(count2, 1);
+        assert_eq!(count3, 0);
+        assert_eq!(get1.as_mut().poll(&mut Context::from_waker(&waker1)), Poll::Ready(&7));
+        assert_eq!(get2.as_mut().poll(&mut Context::from_waker(&waker2)), Poll::Ready(&7));
+    }
+
+    #[futures_test::test]
+    async fn get_or_init_runs_once() {
+        let executor = ThreadPool::new().unwrap();
+
+        static LOCK: OnceLock<u32> = OnceLock::new();
+        let other = executor
+            .spawn_with_handle(async {
+                *LOCK
+                    .get_or_init(|| async {
+                        Delay::new(Duration::from_millis(100)).await;
+                        1
+                    })
+                    .await
+            })
+            .unwrap();
+
+        Delay::new(Duration::from_millis(10)).await;
+        // The other task is initializing, so this initializer does not run.
+        assert_eq!(LOCK.get_or_init(|| async { 2 }).await, &1);
+        assert_eq!(other.await, 1);
+    }
+
+    #[futures_test::test]
+    async fn get_or_init_cancelled() {
+        let lock = OnceLock::<_>::new();
+        {
+            let init = lock.get_or_init(futures_util::future::pending::<u32>);
+            futures_util::pin_mut!(init);
+            assert!(futures_util::poll!(init.as_mut()).is_pending());
+        }
+        assert_eq!(lock.get_or_init(|| async { 3 }).await, &3);
+    }
+
+    #[test]
+    fn drops_value() {
+        struct Counted<'a>(&'a Cell<u32>);
+
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let lock = OnceLock::<_>::new();
+        assert!(lock.init(Counted(&drops)).is_ok());
+        assert!(lock.init(Counted(&drops)).is_err());
+        assert_eq!(drops.get(), 1);
+        drop(lock);
+        assert_eq!(drops.get(), 2);
+    }
+}
@@ -1,7 +1,13 @@
 //! Async byte stream pipe.
-
-use core::cell::RefCell;
-use core::future::Future;
+//!
+//! Besides copying data in and out with [`Pipe::read`] and [`Pipe::write`], a [`Reader`] and a
+//! [`Writer`] give direct access to the pipe's buffer: [`Reader::fill_buf`] returns the buffered
+//! bytes, so they can be decoded in place, and [`Writer::write_buf`] returns free space to produce
+//! data into.
+
+use core::cell::{RefCell, UnsafeCell};
+use core::future::{poll_fn, Future};
+use core::ops::Range;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
@@ -36,14 +42,65 @@ where
     ///
     /// See [`Pipe::write()`]
     pub fn write<'a>(&'a self, buf: &'a [u8]) -> WriteFuture<'a, M, N> {
-        self.pipe.write(buf)
+        WriteFuture {
+            pipe: self.pipe,
+            buf,
+            writer: Some(self.id()),
+        }
     }
 
     /// Attempt to immediately write a message.
     ///
     /// See [`Pipe::write()`]
     pub fn try_write(&self, buf: &[u8]) -> Result<usize, TryWriteError> {
-        self.pipe.try_write(buf)
+        self.pipe.try_write_with_context(Some(self.id()), None, buf)
+    }
+
+    /// Wait until there is free space in the pipe and return it for writing.
+    ///
+    /// The returned slice is at least one byte long. It is a contiguous part of the pipe's
+    /// buffer, so it can be shorter than [`free_capacity`](Pipe::free_capacity) when the free
+    /// space wraps around the end of the buffer.
+    ///
+    /// Nothing is sent to the reader until the written bytes are passed to
+    /// [`commit`](Writer::commit). Until then, other writers of the pipe wait.
+    pub async fn write_buf(&mut self) -> &mut [u8] {
+        let (pipe, id) = (self.pipe, self.id());
+        poll_fn(|cx| {
+            // Safety: `self` is borrowed as long as the slice is alive.
+            match unsafe { pipe.try_write_buf_with_context(id, Some(cx)) } {
+                Ok(buf) => Poll::Ready(buf),
+                Err(TryWriteError::Full) => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Attempt to immediately get free space in the pipe for writing.
+    ///
+    /// See [`write_buf`](Writer::write_buf)
+    pub fn try_write_buf(&mut self) -> Result<&mut [u8], TryWriteError> {
+        // Safety: `self` is borrowed as long as the slice is alive.
+        unsafe { self.pipe.try_write_buf_with_context(self.id(), None) }
+    }
+
+    /// Make the first `amt` bytes of the slice returned by [`write_buf`](Writer::write_buf)
+    /// available to the reader.
+    ///
+    /// If the pipe was [cleared](Pipe::clear) in the meantime, the bytes are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amt` is larger than the slice returned by `write_buf`, or if another writer
+    /// is using the buffer.
+    pub fn commit(&mut self, amt: usize) {
+        self.pipe.lock(|c| c.commit(self.id(), amt))
+    }
+
+    /// Identifies this handle to the pipe. A slice lent out by the pipe borrows the handle, so
+    /// while it is alive the handle can't move and no other handle has the same address.
+    fn id(&self) -> usize {
+        self as *const Self as usize
     }
 }
 
@@ -55,6 +112,7 @@ where
 {
     pipe: &'p Pipe<M, N>,
     buf: &'p [u8],
+    writer: Option<usize>,
 }
 
 impl<'p, M, const N: usize> Future for WriteFuture<'p, M, N>
@@ -64,7 +122,7 @@ where
     type Output = usize;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.pipe.try_write_with_context(Some(cx), self.buf) {
+        match self.pipe.try_write_with_context(self.writer, Some(cx), self.buf) {
             Ok(n) => Poll::Ready(n),
             Err(TryWriteError::Full) => Poll::Pending,
         }
@@ -99,14 +157,63 @@ where
     ///
     /// See [`Pipe::read()`]
     pub fn read<'a>(&'a self, buf: &'a mut [u8]) -> ReadFuture<'a, M, N> {
-        self.pipe.read(buf)
+        ReadFuture {
+            pipe: self.pipe,
+            buf,
+            reader: Some(self.id()),
+        }
     }
 
     /// Attempt to immediately read a message.
     ///
     /// See [`Pipe::read()`]
     pub fn try_read(&self, buf: &mut [u8]) -> Result<usize, TryReadError> {
-        self.pipe.try_read(buf)
+        self.pipe.try_read_with_context(Some(self.id()), None, buf)
+    }
+
+    /// Wait until data is available and return it without copying.
+    ///
+    /// The returned slice is at least one byte long. It is a contiguous part of the pipe's
+    /// buffer, so it can be shorter than [`len`](Pipe::len) when the buffered data wraps
+    /// around the end of the buffer. The rest is returned once the slice has been consumed.
+    ///
+    /// Data stays in the pipe until it is marked as read with [`consume`](Reader::consume).
+    /// Until then, other readers of the pipe wait.
+    pub async fn fill_buf(&mut self) -> &[u8] {
+        let (pipe, id) = (self.pipe, self.id());
+        poll_fn(|cx| {
+            // Safety: `self` is borrowed as long as the slice is alive.
+            match unsafe { pipe.try_fill_buf_with_context(id, Some(cx)) } {
+                Ok(buf) => Poll::Ready(buf),
+                Err(TryReadError::Empty) => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Attempt to immediately get the data available in the pipe without copying.
+    ///
+    /// See [`fill_buf`](Reader::fill_buf)
+    pub fn try_fill_buf(&mut self) -> Result<&[u8], TryReadError> {
+        // Safety: `self` is borrowed as long as the slice is alive.
+        unsafe { self.pipe.try_fill_buf_with_context(self.id(), None) }
+    }
+
+    /// Remove the first `amt` bytes of the slice returned by [`fill_buf`](Reader::fill_buf)
+    /// from the pipe.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amt` is larger than the slice returned by `fill_buf`, or if another reader
+    /// is using the buffer.
+    pub fn consume(&mut self, amt: usize) {
+        self.pipe.lock(|c| c.consume(self.id(), amt))
+    }
+
+    /// Identifies this handle to the pipe. A slice lent out by the pipe borrows the handle, so
+    /// while it is alive the handle can't move and no other handle has the same address.
+    fn id(&self) -> usize {
+        self as *const Self as usize
     }
 }
 
@@ -118,6 +225,7 @@ where
 {
     pipe: &'p Pipe<M, N>,
     buf: &'p mut [u8],
+    reader: Option<usize>,
 }
 
 impl<'p, M, const N: usize> Future for ReadFuture<'p, M, N>
@@ -127,7 +235,8 @@ where
     type Output = usize;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.pipe.try_read_with_context(Some(cx), self.buf) {
+        let reader = self.reader;
+        match self.pipe.try_read_with_context(reader, Some(cx), self.buf) {
             Ok(n) => Poll::Ready(n),
             Err(TryReadError::Empty) => Poll::Pending,
         }
@@ -154,10 +263,55 @@ pub enum TryWriteError {
     Full,
 }
 
+/// The bytes of a pipe.
+///
+/// They are kept out of the [`PipeState`], so that parts of them can be lent out while the state
+/// changes. The state keeps track of which parts can be accessed.
+struct Buffer<const N: usize>(UnsafeCell<[u8; N]>);
+
+// Accesses are coordinated by the pipe's state, see `get` and `get_mut`.
+unsafe impl<const N: usize> Sync for Buffer<N> {}
+
+impl<const N: usize> Buffer<N> {
+    const fn new() -> Self {
+        Self(UnsafeCell::new([0; N]))
+    }
+
+    /// # Safety
+    ///
+    /// No one may write to `range` while the returned slice is alive.
+    unsafe fn get<'a>(&self, range: Range<usize>) -> &'a [u8] {
+        core::slice::from_raw_parts((self.0.get() as *const u8).add(range.start), range.len())
+    }
+
+    /// # Safety
+    ///
+    /// No one else may access `range` while the returned slice is alive.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn get_mut<'a>(&self, range: Range<usize>) -> &'a mut [u8] {
+        core::slice::from_raw_parts_mut((self.0.get() as *mut u8).add(range.start), range.len())
+    }
+}
+
+/// Part of the buffer lent out to a [`Reader`] or a [`Writer`].
+#[derive(Clone, Copy)]
+struct Lease {
+    /// Address of the handle, see `Reader::id`.
+    owner: usize,
+    len: usize,
+    /// Set when the pipe is cleared while space is lent to a writer, whose bytes are then
+    /// dropped.
+    cleared: bool,
+}
+
 struct PipeState<const N: usize> {
     buffer: RingBuffer<N>,
     read_waker: WakerRegistration,
     write_waker: WakerRegistration,
+    /// Data lent out by `Reader::fill_buf`. Only its owner can read it.
+    read_lease: Option<Lease>,
+    /// Free space lent out by `Writer::write_buf`. Only its owner can write.
+    write_lease: Option<Lease>,
 }
 
 impl<const N: usize> PipeState<N> {
@@ -166,59 +320,179 @@ impl<const N: usize> PipeState<N> {
             buffer: RingBuffer::new(),
             read_waker: WakerRegistration::new(),
             write_waker: WakerRegistration::new(),
+            read_lease: None,
+            write_lease: None,
         }
     }
 
     fn clear(&mut self) {
-        self.buffer.clear();
+        match self.read_lease {
+            // Keep the data lent out, the reader may still be using it.
+            Some(lease) => self.buffer.truncate(lease.len),
+            None if self.write_lease.is_some() => self.buffer.truncate(0),
+            None => self.buffer.clear(),
+        }
+        if let Some(lease) = &mut self.write_lease {
+            lease.cleared = true;
+        }
         self.write_waker.wake();
     }
 
-    fn try_read(&mut self, buf: &mut [u8]) -> Result<usize, TryReadError> {
-        self.try_read_with_context(None, buf)
+    /// End the lease of `reader`. Returns false if another reader has a lease.
+    fn end_read_lease(&mut self, reader: Option<usize>) -> bool {
+        match self.read_lease {
+            Some(lease) if Some(lease.owner) != reader => false,
+            Some(_) => {
+                self.read_lease = None;
+                // Wake the readers waiting for the lease to end.
+                self.read_waker.wake();
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// End the lease of `writer`. Returns false if another writer has a lease.
+    fn end_write_lease(&mut self, writer: Option<usize>) -> bool {
+        match self.write_lease {
+            Some(lease) if Some(lease.owner) != writer => false,
+            Some(_) => {
+                self.write_lease = None;
+                // Wake the writers waiting for the lease to end.
+                self.write_waker.wake();
+                true
+            }
+            None => true,
+        }
     }
 
-    fn try_read_with_context(&mut self, cx: Option<&mut Context<'_>>, buf: &mut [u8]) -> Result<usize, TryReadError> {
+    /// Get the data that `reader` may read, waiting with `cx` if there is none.
+    fn readable(&mut self, reader: Option<usize>, cx: Option<&mut Context<'_>>) -> Result<Range<usize>, TryReadError> {
         if self.buffer.is_full() {
             self.write_waker.wake();
         }
 
         let available = self.buffer.pop_buf();
-        if available.is_empty() {
+        if !self.end_read_lease(reader) || available.is_empty() {
             if let Some(cx) = cx {
                 self.read_waker.register(cx.waker());
             }
             return Err(TryReadError::Empty);
         }
-
-        let n = available.len().min(buf.len());
-        buf[..n].copy_from_slice(&available[..n]);
-        self.buffer.pop(n);
-        Ok(n)
+        Ok(available)
     }
 
-    fn try_write(&mut self, buf: &[u8]) -> Result<usize, TryWriteError> {
-        self.try_write_with_context(None, buf)
-    }
-
-    fn try_write_with_context(&mut self, cx: Option<&mut Context<'_>>, buf: &[u8]) -> Result<usize, TryWriteError> {
+    /// Get the free space that `writer` may write to, waiting with `cx` if there is none.
+    fn writable(&mut self, writer: Option<usize>, cx: Option<&mut Context<'_>>) -> Result<Range<usize>, TryWriteError> {
         if self.buffer.is_empty() {
             self.read_waker.wake();
         }
 
         let available = self.buffer.push_buf();
-        if available.is_empty() {
+        if !self.end_write_lease(writer) || available.is_empty() {
             if let Some(cx) = cx {
                 self.write_waker.register(cx.waker());
             }
             return Err(TryWriteError::Full);
         }
+        Ok(available)
+    }
+
+    fn try_read_with_context(
+        &mut self,
+        bytes: &Buffer<N>,
+        reader: Option<usize>,
+        cx: Option<&mut Context<'_>>,
+        buf: &mut [u8],
+    ) -> Result<usize, TryReadError> {
+        let available = self.readable(reader, cx)?;
+        // Safety: the data is not lent out, and only writers, which don't write to data, could
+        // change it.
+        let available = unsafe { bytes.get(available) };
+
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.buffer.pop(n);
+        Ok(n)
+    }
+
+    fn try_write_with_context(
+        &mut self,
+        bytes: &Buffer<N>,
+        writer: Option<usize>,
+        cx: Option<&mut Context<'_>>,
+        buf: &[u8],
+    ) -> Result<usize, TryWriteError> {
+        let available = self.writable(writer, cx)?;
+        // Safety: the free space is not lent out, and only readers, which don't access free
+        // space, could use the buffer meanwhile.
+        let available = unsafe { bytes.get_mut(available) };
 
         let n = available.len().min(buf.len());
         available[..n].copy_from_slice(&buf[..n]);
         self.buffer.push(n);
         Ok(n)
     }
+
+    fn fill_buf_with_context(
+        &mut self,
+        reader: usize,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<Range<usize>, TryReadError> {
+        let available = self.readable(Some(reader), cx)?;
+        self.read_lease = Some(Lease {
+            owner: reader,
+            len: available.len(),
+            cleared: false,
+        });
+        Ok(available)
+    }
+
+    fn consume(&mut self, reader: usize, amt: usize) {
+        assert!(
+            self.end_read_lease(Some(reader)),
+            "another reader is using the pipe's buffer"
+        );
+        assert!(amt <= self.buffer.pop_buf().len());
+        if self.buffer.is_full() {
+            self.write_waker.wake();
+        }
+        self.buffer.pop(amt);
+    }
+
+    fn write_buf_with_context(
+        &mut self,
+        writer: usize,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<Range<usize>, TryWriteError> {
+        let available = self.writable(Some(writer), cx)?;
+        self.write_lease = Some(Lease {
+            owner: writer,
+            len: available.len(),
+            cleared: false,
+        });
+        Ok(available)
+    }
+
+    fn commit(&mut self, writer: usize, amt: usize) {
+        let lease = self.write_lease;
+        assert!(
+            self.end_write_lease(Some(writer)),
+            "another writer is using the pipe's buffer"
+        );
+
+        if let Some(lease) = lease {
+            assert!(amt <= lease.len);
+            if lease.cleared {
+                return;
+            }
+        }
+        assert!(amt <= self.buffer.push_buf().len());
+        if self.buffer.is_empty() {
+            self.read_waker.wake();
+        }
+        self.buffer.push(amt);
+    }
 }
 
 /// A bounded pipe for communicating between asynchronous tasks
@@ -233,6 +507,7 @@ pub struct Pipe<M, const N: usize>
 where
     M: RawMutex,
 {
+    buf: Buffer<N>,
     inner: Mutex<M, RefCell<PipeState<N>>>,
 }
 
@@ -251,6 +526,7 @@ where
     /// ```
     pub const fn new() -> Self {
         Self {
+            buf: Buffer::new(),
             inner: Mutex::new(RefCell::new(PipeState::new())),
         }
     }
@@ -259,12 +535,52 @@ where
         self.inner.lock(|rc| f(&mut *rc.borrow_mut()))
     }
 
-    fn try_read_with_context(&self, cx: Option<&mut Context<'_>>, buf: &mut [u8]) -> Result<usize, TryReadError> {
-        self.lock(|c| c.try_read_with_context(cx, buf))
+    fn try_read_with_context(
+        &self,
+        reader: Option<usize>,
+        cx: Option<&mut Context<'_>>,
+        buf: &mut [u8],
+    ) -> Result<usize, TryReadError> {
+        self.lock(|c| c.try_read_with_context(&self.buf, reader, cx, buf))
     }
 
-    fn try_write_with_context(&self, cx: Option<&mut Context<'_>>, buf: &[u8]) -> Result<usize, TryWriteError> {
-        self.lock(|c| c.try_write_with_context(cx, buf))
+    fn try_write_with_context(
+        &self,
+        writer: Option<usize>,
+        cx: Option<&mut Context<'_>>,
+        buf: &[u8],
+    ) -> Result<usize, TryWriteError> {
+        self.lock(|c| c.try_write_with_context(&self.buf, writer, cx, buf))
+    }
+
+    /// # Safety
+    ///
+    /// `reader` must be the address of a reader that stays borrowed while the returned slice
+    /// is alive.
+    unsafe fn try_fill_buf_with_context<'a>(
+        &self,
+        reader: usize,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<&'a [u8], TryReadError> {
+        // The data is lent to the reader, so it is neither read by others nor overwritten
+        // until the reader is used again.
+        let available = self.lock(|c| c.fill_buf_with_context(reader, cx))?;
+        Ok(self.buf.get(available))
+    }
+
+    /// # Safety
+    ///
+    /// `writer` must be the address of a writer that stays borrowed while the returned slice
+    /// is alive.
+    unsafe fn try_write_buf_with_context<'a>(
+        &self,
+        writer: usize,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<&'a mut [u8], TryWriteError> {
+        // The free space is lent to the writer, so no one else writes to it until the writer
+        // is used again, and readers don't access free space.
+        let available = self.lock(|c| c.write_buf_with_context(writer, cx))?;
+        Ok(self.buf.get_mut(available))
     }
 
     /// Get a writer for this pipe.
@@ -277,12 +593,21 @@ where
         Reader { pipe: self }
     }
 
+    /// Get a reader and a writer for this pipe.
+    pub fn split(&self) -> (Reader<'_, M, N>, Writer<'_, M, N>) {
+        (self.reader(), self.writer())
+    }
+
     /// Write a value, waiting until there is capacity.
     ///
     /// Writeing completes when the value has been pushed to the pipe's queue.
     /// This doesn't mean the value has been read yet.
     pub fn write<'a>(&'a self, buf: &'a [u8]) -> WriteFuture<'a, M, N> {
-        WriteFuture { pipe: self, buf }
+        WriteFuture {
+            pipe: self,
+            buf,
+            writer: None,
+        }
     }
 
     /// Attempt to immediately write a message.
@@ -296,7 +621,7 @@ where
     /// buffered values where `n` is the argument passed to [`Pipe`], then an
     /// error is returned.
     pub fn try_write(&self, buf: &[u8]) -> Result<usize, TryWriteError> {
-        self.lock(|c| c.try_write(buf))
+        self.try_write_with_context(None, None, buf)
     }
 
     /// Receive the next value.
@@ -304,7 +629,11 @@ where
     /// If there are no messages in the pipe's buffer, this method will
     /// wait until a message is written.
     pub fn read<'a>(&'a self, buf: &'a mut [u8]) -> ReadFuture<'a, M, N> {
-        ReadFuture { pipe: self, buf }
+        ReadFuture {
+            pipe: self,
+            buf,
+            reader: None,
+        }
     }
 
     /// Attempt to immediately read a message.
@@ -312,10 +641,12 @@ where
     /// This method will either read a message from the pipe immediately or return an error
     /// if the pipe is empty.
     pub fn try_read(&self, buf: &mut [u8]) -> Result<usize, TryReadError> {
-        self.lock(|c| c.try_read(buf))
+        self.try_read_with_context(None, None, buf)
     }
 
     /// Clear the data in the pipe's buffer.
+    ///
+    /// Data lent out by [`Reader::fill_buf`] is kept until it is consumed.
     pub fn clear(&self) {
         self.lock(|c| c.clear())
     }
@@ -406,6 +737,16 @@ mod io_impls {
         }
     }
 
+    impl<M: RawMutex, const N: usize> embedded_io::asynch::BufRead for Reader<'_, M, N> {
+        async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+            Ok(Reader::fill_buf(self).await)
+        }
+
+        fn consume(&mut self, amt: usize) {
+            Reader::consume(self, amt)
+        }
+    }
+
     impl<M: RawMutex, const N: usize> embedded_io::Io for Writer<'_, M, N> {
         type Error = Infallible;
     }
@@ -430,20 +771,20 @@ mod tests {
     use super::*;
     use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
 
-    fn capacity<const N: usize>(c: &PipeState<N>) -> usize {
-        N - c.buffer.len()
+    fn capacity<const N: usize>(c: &Pipe<NoopRawMutex, N>) -> usize {
+        N - c.lock(|c| c.buffer.len())
     }
 
     #[test]
     fn writing_once() {
-        let mut c = PipeState::<3>::new();
+        let c = Pipe::<NoopRawMutex, 3>::new();
         assert!(c.try_write(&[1]).is_ok());
         assert_eq!(capacity(&c), 2);
     }
 
     #[test]
     fn writing_when_full() {
-        let mut c = PipeState::<3>::new();
+        let c = Pipe::<NoopRawMutex, 3>::new();
         assert_eq!(c.try_write(&[42]), Ok(1));
         assert_eq!(c.try_write(&[43]), Ok(1));
         assert_eq!(c.try_write(&[44]), Ok(1));
@@ -453,7 +794,7 @@ mod tests {
 
     #[test]
     fn receiving_once_with_one_send() {
-        let mut c = PipeState::<3>::new();
+        let c = Pipe::<NoopRawMutex, 3>::new();
         assert!(c.try_write(&[42]).is_ok());
         let mut buf = [0; 16];
         assert_eq!(c.try_read(&mut buf), Ok(1));
@@ -463,7 +804,7 @@ mod tests {
 
     #[test]
     fn receiving_when_empty() {
-        let mut c = PipeState::<3>::new();
+        let c = Pipe::<NoopRawMutex, 3>::new();
         let mut buf = [0; 16];
         assert_eq!(c.try_read(&mut buf), Err(TryReadError::Empty));
         assert_eq!(capacity(&c), 3);
@@ -512,4 +853,144 @@ mod tests {
         assert_eq!(c.read(&mut buf).await, 1);
         assert_eq!(buf[0], 42);
     }
+
+    #[test]
+    fn splitting() {
+        let c = Pipe::<NoopRawMutex, 3>::new();
+        let (r, w) = c.split();
+
+        assert_eq!(w.try_write(&[42]), Ok(1));
+        let mut buf = [0; 16];
+        assert_eq!(r.try_read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 42);
+    }
+
+    #[test]
+    fn zero_copy() {
+        let c = Pipe::<NoopRawMutex, 4>::new();
+        let (mut r, mut w) = c.split();
+        assert_eq!(r.try_fill_buf(), Err(TryReadError::Empty));
+
+        let buf = w.try_write_buf().unwrap();
+        assert_eq!(buf.len(), 4);
+        buf[..3].copy_from_slice(&[1, 2, 3]);
+        // Nothing is readable before committing.
+        assert_eq!(r.try_fill_buf(), Err(TryReadError::Empty));
+        w.commit(3);
+
+        assert_eq!(r.try_fill_buf(), Ok(&[1, 2, 3][..]));
+        r.consume(2);
+        assert_eq!(r.try_fill_buf(), Ok(&[3][..]));
+
+        // The free space wraps around the end of the buffer.
+        let buf = w.try_write_buf().unwrap();
+        assert_eq!(buf.len(), 1);
+        buf[0] = 4;
+        w.commit(1);
+        let buf = w.try_write_buf().unwrap();
+        assert_eq!(buf.len(), 2);
+        buf[0] = 5;
+        w.commit(1);
+
+        assert_eq!(r.try_fill_buf(), Ok(&[3, 4][..]));
+        r.consume(2);
+        assert_eq!(r.try_fill_buf(), Ok(&[5][..]));
+        r.consume(1);
+        assert_eq!(r.try_fill_buf(), Err(TryReadError::Empty));
+    }
+
+    #[test]
+    #[should_panic]
+    fn consuming_too_much() {
+        let c = Pipe::<NoopRawMutex, 4>::new();
+        let (mut r, w) = c.split();
+        assert_eq!(w.try_write(&[1, 2]), Ok(2));
+        r.consume(3);
+    }
+
+    #[test]
+    fn lent_data_is_not_read_by_others() {
+        let c = Pipe::<NoopRawMutex, 4>::new();
+        let (mut r1, w) = c.split();
+        let mut r2 = c.reader();
+        assert_eq!(w.try_write(&[1, 2]), Ok(2));
+
+        assert_eq!(r1.try_fill_buf(), Ok(&[1, 2][..]));
+        let mut buf = [0; 4];
+        assert_eq!(r2.try_fill_buf(), Err(TryReadError::Empty));
+        assert_eq!(r2.try_read(&mut buf), Err(TryReadError::Empty));
+        assert_eq!(c.try_read(&mut buf), Err(TryReadError::Empty));
+
+        r1.consume(1);
+        assert_eq!(r2.try_read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn consuming_data_lent_to_another_reader() {
+        let c = Pipe::<NoopRawMutex, 4>::new();
+        let (mut r1, w) = c.split();
+        let mut r2 = c.reader();
+        assert_eq!(w.try_write(&[1, 2]), Ok(2));
+
+        assert_eq!(r1.try_fill_buf(), Ok(&[1, 2][..]));
+        r2.consume(1);
+    }
+
+    #[test]
+    fn lent_space_is_not_written_by_others() {
+        let c = Pipe::<NoopRawMutex, 4>::new();
+        let (r, mut w1) = c.split();
+        let mut w2 = c.writer();
+
+        w1.try_write_buf().unwrap()[0] = 1;
+        assert_eq!(w2.try_write_buf().err(), Some(TryWriteError::Full));
+        assert_eq!(w2.try_write(&[2]), Err(TryWriteError::Full));
+        assert_eq!(c.try_write(&[2]), Err(TryWriteError::Full));
+
+        w1.commit(1);
+        assert_eq!(w2.try_write(&[2]), Ok(1));
+        let mut buf = [0; 4];
+        assert_eq!(r.try_read(&mut buf), Ok(2));
+        assert_eq!(buf[..2], [1, 2]);
+    }
+
+    #[test]
+    fn clearing_keeps_lent_data() {
+        let c = Pipe::<NoopRawMutex, 4>::new();
+        let (mut r, mut w) = c.split();
+        assert_eq!(w.try_write(&[1, 2, 3]), Ok(3));
+
+        assert_eq!(r.try_fill_buf(), Ok(&[1, 2, 3][..]));
+        w.try_write_buf().unwrap()[0] = 4;
+        c.clear();
+        // The lent data can't be overwritten, and the uncommitted byte is dropped.
+        w.commit(1);
+        assert_eq!(c.len(), 3);
+
+        r.consume(3);
+        assert!(c.is_empty());
+        assert_eq!(w.try_write(&[5]), Ok(1));
+        assert_eq!(r.try_fill_buf(), Ok(&[5][..]));
+    }
+
+    #[futures_test::test]
+    async fn fill_buf_waits_for_data() {
+        let executor = ThreadPool::new().unwrap();
+
+        static PIPE: StaticCell<Pipe<CriticalSectionRawMutex, 8>> = StaticCell::new();
+        let (mut r, mut w) = PIPE.init(Pipe::new()).split();
+
+        executor
+            .spawn(async move {
+                let buf = w.write_buf().await;
+                buf[..2].copy_from_slice(&[1, 2]);
+                w.commit(2);
+            })
+            .unwrap();
+
+        assert_eq!(r.fill_buf().await, &[1, 2]);
+        r.consume(2);
+    }
 }
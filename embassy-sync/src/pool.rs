@@ -0,0 +1,239 @@
+//! A pool of objects that tasks can take out and wait for.
+
+use core::cell::{RefCell, UnsafeCell};
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::task::Poll;
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A pool of `N` objects of type `T`.
+///
+/// Objects are taken out of the pool with [`alloc`](Pool::alloc), which waits until an object is
+/// available, or [`try_alloc`](Pool::try_alloc). They are handed out as a [`PoolBox`], which
+/// returns the object to the pool when dropped.
+///
+/// The objects live in the pool for its entire lifetime, and keep their contents when they are
+/// returned. This makes the pool useful for things like DMA buffers or request slots, which are
+/// expensive to create or move around.
+///
+/// Up to `N` waiting tasks are registered at a time. If more tasks are waiting, the registered ones
+/// are woken to make room, and register again when they are polled.
+///
+/// ## Example
+///
+/// ```
+/// # use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// # use embassy_sync::pool::Pool;
+/// # use futures_executor::block_on;
+/// # let test = async {
+/// let pool = Pool::<NoopRawMutex, [u8; 4], 2>::new([[0; 4]; 2]);
+///
+/// let mut a = pool.alloc().await;
+/// a.copy_from_slice(&[1, 2, 3, 4]);
+/// let b = pool.try_alloc().unwrap();
+///
+/// // All objects are taken
+/// assert!(pool.try_alloc().is_none());
+///
+/// // Dropping a box returns the object to the pool
+/// drop(b);
+/// assert_eq!(pool.available(), 1);
+/// # };
+/// #
+/// # block_on(test);
+/// ```
+pub struct Pool<M: RawMutex, T, const N: usize> {
+    items: UnsafeCell<[T; N]>,
+    state: Mutex<M, RefCell<State<N>>>,
+}
+
+struct State<const N: usize> {
+    /// Which objects are handed out
+    used: [bool; N],
+    wakers: MultiWakerRegistration<N>,
+}
+
+unsafe impl<M: RawMutex + Sync, T: Send, const N: usize> Sync for Pool<M, T, N> {}
+
+impl<M: RawMutex, T, const N: usize> Pool<M, T, N> {
+    /// Create a new pool containing `items`.
+    pub const fn new(items: [T; N]) -> Self {
+        Self {
+            items: UnsafeCell::new(items),
+            state: Mutex::new(RefCell::new(State {
+                used: [false; N],
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut State<N>) -> R) -> R {
+        self.state.lock(|s| f(&mut s.borrow_mut()))
+    }
+
+    /// Take an object out of the pool, waiting until one is available.
+    pub async fn alloc(&self) -> PoolBox<'_, M, T, N> {
+        poll_fn(|cx| {
+            self.lock(|s| match Self::take(s) {
+                Some(index) => Poll::Ready(PoolBox::new(self, index)),
+                None => {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    /// Take an object out of the pool if one is available.
+    pub fn try_alloc(&self) -> Option<PoolBox<'_, M, T, N>> {
+        let index = self.lock(Self::take)?;
+        Some(PoolBox::new(self, index))
+    }
+
+    /// The amount of objects in the pool that are not taken.
+    pub fn available(&self) -> usize {
+        self.lock(|s| s.used.iter().filter(|used| !**used).count())
+    }
+
+    /// The total amount of objects in the pool.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    fn take(s: &mut State<N>) -> Option<usize> {
+        let index = s.used.iter().position(|used| !used)?;
+        s.used[index] = true;
+        Some(index)
+    }
+
+    fn release(&self, index: usize) {
+        self.lock(|s| {
+            s.used[index] = false;
+            s.wakers.wake();
+        })
+    }
+}
+
+/// An object taken out of a [`Pool`]. It is returned to the pool when dropped.
+pub struct PoolBox<'a, M: RawMutex, T, const N: usize> {
+    pool: &'a Pool<M, T, N>,
+    index: usize,
+    _phantom: PhantomData<&'a mut T>,
+}
+
+impl<'a, M: RawMutex, T, const N: usize> PoolBox<'a, M, T, N> {
+    fn new(pool: &'a Pool<M, T, N>, index: usize) -> Self {
+        Self {
+            pool,
+            index,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn item(&self) -> *mut T {
+        // Safety: `index` is in bounds of the items.
+        unsafe { (self.pool.items.get() as *mut T).add(self.index) }
+    }
+}
+
+impl<'a, M: RawMutex, T, const N: usize> Deref for PoolBox<'a, M, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the object is marked as used, so this is the only box referring to it.
+        unsafe { &*self.item() }
+    }
+}
+
+impl<'a, M: RawMutex, T, const N: usize> DerefMut for PoolBox<'a, M, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: the object is marked as used, so this is the only box referring to it.
+        unsafe { &mut *self.item() }
+    }
+}
+
+impl<'a, M: RawMutex, T, const N: usize> Drop for PoolBox<'a, M, T, N> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::Context;
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+
+    use super::*;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    #[test]
+    fn objects_keep_contents() {
+        let pool = Pool::<NoopRawMutex, u32, 2>::new([0, 0]);
+        assert_eq!(pool.capacity(), 2);
+
+        let mut a = pool.try_alloc().unwrap();
+        let mut b = pool.try_alloc().unwrap();
+        assert!(pool.try_alloc().is_none());
+        *a = 1;
+        *b = 2;
+
+        drop(a);
+        assert_eq!(pool.available(), 1);
+        assert_eq!(*pool.try_alloc().unwrap(), 1);
+        assert_eq!(*b, 2);
+    }
+
+    #[futures_test::test]
+    async fn alloc_waits_for_object() {
+        let executor = ThreadPool::new().unwrap();
+
+        static POOL: Pool<CriticalSectionRawMutex, u32, 1> = Pool::new([0]);
+
+        let mut object = POOL.alloc().await;
+        *object = 42;
+        executor
+            .spawn(async move {
+                Delay::new(Duration::from_millis(100)).await;
+                drop(object);
+            })
+            .unwrap();
+
+        assert_eq!(*POOL.alloc().await, 42);
+    }
+
+    #[test]
+    fn waiters_do_not_wake_each_other() {
+        let pool = Pool::<NoopRawMutex, u32, 2>::new([0, 0]);
+        let a = pool.try_alloc().unwrap();
+        let _b = pool.try_alloc().unwrap();
+        let (waker1, count1) = futures_test::task::new_count_waker();
+        let (waker2, count2) = futures_test::task::new_count_waker();
+
+        let mut first = pin!(pool.alloc());
+        let mut second = pin!(pool.alloc());
+        assert!(first.as_mut().poll(&mut Context::from_waker(&waker1)).is_pending());
+        assert!(second.as_mut().poll(&mut Context::from_waker(&waker2)).is_pending());
+        assert!(first.as_mut().poll(&mut Context::from_waker(&waker1)).is_pending());
+        assert_eq!(count1, 0);
+        assert_eq!(count2, 0);
+
+        drop(a);
+        assert_eq!(count1, 1);
+        assert_eq!(count2, 1);
+        let Poll::Ready(_a) = first.as_mut().poll(&mut Context::from_waker(&waker1)) else {
+            panic!("the released object was not handed out");
+        };
+        assert!(second.as_mut().poll(&mut Context::from_waker(&waker2)).is_pending());
+    }
+}
@@ -12,7 +12,7 @@ use self::publisher::{ImmediatePub, Pub};
 use self::subscriber::Sub;
 use crate::blocking_mutex::raw::RawMutex;
 use crate::blocking_mutex::Mutex;
-use crate::waitqueue::MultiWakerRegistration;
+use crate::waitqueue::{MultiWakerRegistration, WakerRegistration};
 
 pub mod publisher;
 pub mod subscriber;
@@ -25,10 +25,16 @@ pub use subscriber::{DynSubscriber, Subscriber};
 /// Any published message can be read by all subscribers.
 /// A publisher can choose how it sends its message.
 ///
-/// - With [Pub::publish()] the publisher has to wait until there is space in the internal message queue.
+/// - With [Pub::publish()] the queue being full is handled according to the [`LagPolicy`] of the
+///   subscribers that have not received the oldest message yet. By default, subscribers use
+///   [`LagPolicy::Block`], so the publisher has to wait until there is space in the internal message queue.
 /// - With [Pub::publish_immediate()] the publisher doesn't await and instead lets the oldest message
-/// in the queue drop if necessary. This will cause any [Subscriber] that missed the message to receive
-/// an error to indicate that it has lagged.
+///   in the queue drop if necessary. This will cause any [Subscriber] that missed the message to receive
+///   an error to indicate that it has lagged.
+///
+/// Subscribers can be created with a [`Filter`] using [`subscriber_with`](PubSubChannel::subscriber_with).
+/// Messages a subscriber is not interested in are not delivered to it, and don't take up space in the
+/// queue on its behalf.
 ///
 /// ## Example
 ///
@@ -49,7 +55,7 @@ pub use subscriber::{DynSubscriber, Subscriber};
 /// let pub0 = channel.publisher().unwrap();
 ///
 /// // Publish a message, but wait if the queue is full
-/// pub0.publish(42).await;
+/// pub0.publish(42).await.unwrap();
 ///
 /// // Publish a message, but if the queue is full, just kick out the oldest message.
 /// // This may cause some subscribers to miss a message
@@ -87,34 +93,42 @@ impl<M: RawMutex, T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usi
 
     /// Create a new subscriber. It will only receive messages that are published after its creation.
     ///
+    /// The subscriber receives all messages and uses [`LagPolicy::Block`].
+    ///
     /// If there are no subscriber slots left, an error will be returned.
     pub fn subscriber(&self) -> Result<Subscriber<M, T, CAP, SUBS, PUBS>, Error> {
-        self.inner.lock(|inner| {
-            let mut s = inner.borrow_mut();
+        self.subscriber_with(Filter::All, LagPolicy::Block)
+    }
 
-            if s.subscriber_count >= SUBS {
-                Err(Error::MaximumSubscribersReached)
-            } else {
-                s.subscriber_count += 1;
-                Ok(Subscriber(Sub::new(s.next_message_id, self)))
-            }
-        })
+    /// Create a new subscriber that only receives messages matching `filter`, and handles a full
+    /// queue according to `policy`. It will only receive messages that are published after its creation.
+    ///
+    /// If there are no subscriber slots left, an error will be returned.
+    pub fn subscriber_with(
+        &self,
+        filter: Filter<T>,
+        policy: LagPolicy,
+    ) -> Result<Subscriber<'_, M, T, CAP, SUBS, PUBS>, Error> {
+        let slot = self.inner.lock(|inner| inner.borrow_mut().subscribe(filter, policy))?;
+        Ok(Subscriber(Sub::new(slot, self)))
     }
 
     /// Create a new subscriber. It will only receive messages that are published after its creation.
     ///
+    /// The subscriber receives all messages and uses [`LagPolicy::Block`].
+    ///
     /// If there are no subscriber slots left, an error will be returned.
     pub fn dyn_subscriber(&self) -> Result<DynSubscriber<'_, T>, Error> {
-        self.inner.lock(|inner| {
-            let mut s = inner.borrow_mut();
+        self.dyn_subscriber_with(Filter::All, LagPolicy::Block)
+    }
 
-            if s.subscriber_count >= SUBS {
-                Err(Error::MaximumSubscribersReached)
-            } else {
-                s.subscriber_count += 1;
-                Ok(DynSubscriber(Sub::new(s.next_message_id, self)))
-            }
-        })
+    /// Create a new subscriber that only receives messages matching `filter`, and handles a full
+    /// queue according to `policy`. It will only receive messages that are published after its creation.
+    ///
+    /// If there are no subscriber slots left, an error will be returned.
+    pub fn dyn_subscriber_with(&self, filter: Filter<T>, policy: LagPolicy) -> Result<DynSubscriber<'_, T>, Error> {
+        let slot = self.inner.lock(|inner| inner.borrow_mut().subscribe(filter, policy))?;
+        Ok(DynSubscriber(Sub::new(slot, self)))
     }
 
     /// Create a new publisher
@@ -160,40 +174,46 @@ impl<M: RawMutex, T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usi
     pub fn dyn_immediate_publisher(&self) -> DynImmediatePublisher<T> {
         DynImmediatePublisher(ImmediatePub::new(self))
     }
+
+    /// Close the channel.
+    ///
+    /// Subscribers still receive the messages that were published before, after which they receive
+    /// [`WaitResult::Closed`] and their stream ends. Publishing after closing fails, handing the
+    /// message back, except for `publish_immediate` which drops it.
+    pub fn close(&self) {
+        self.inner.lock(|s| s.borrow_mut().close())
+    }
+
+    /// Returns whether the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock(|s| s.borrow().closed)
+    }
 }
 
 impl<M: RawMutex, T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usize> PubSubBehavior<T>
     for PubSubChannel<M, T, CAP, SUBS, PUBS>
 {
-    fn get_message_with_context(&self, next_message_id: &mut u64, cx: Option<&mut Context<'_>>) -> Poll<WaitResult<T>> {
+    fn get_message_with_context(&self, subscriber: usize, cx: Option<&mut Context<'_>>) -> Poll<WaitResult<T>> {
         self.inner.lock(|s| {
             let mut s = s.borrow_mut();
 
             // Check if we can read a message
-            match s.get_message(*next_message_id) {
-                // Yes, so we are done polling
-                Some(WaitResult::Message(message)) => {
-                    *next_message_id += 1;
-                    Poll::Ready(WaitResult::Message(message))
-                }
+            match s.get_message(subscriber) {
+                // Yes (or we lagged), so we are done polling
+                Some(result) => Poll::Ready(result),
                 // No, so we need to reregister our waker and sleep again
                 None => {
                     if let Some(cx) = cx {
-                        s.register_subscriber_waker(cx.waker());
+                        s.subscriber_mut(subscriber).waker.register(cx.waker());
                     }
                     Poll::Pending
                 }
-                // We missed a couple of messages. We must do our internal bookkeeping and return that we lagged
-                Some(WaitResult::Lagged(amount)) => {
-                    *next_message_id += amount;
-                    Poll::Ready(WaitResult::Lagged(amount))
-                }
             }
         })
     }
 
-    fn available(&self, next_message_id: u64) -> u64 {
-        self.inner.lock(|s| s.borrow().next_message_id - next_message_id)
+    fn available(&self, subscriber: usize) -> u64 {
+        self.inner.lock(|s| s.borrow().available(subscriber))
     }
 
     fn publish_with_context(&self, message: T, cx: Option<&mut Context<'_>>) -> Result<(), T> {
@@ -203,6 +223,8 @@ impl<M: RawMutex, T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usi
             match s.try_publish(message) {
                 // We did it, we are ready
                 Ok(()) => Ok(()),
+                // The channel is closed, so the message can't be published at all
+                Err(message) if s.closed => Err(message),
                 // The queue is full, so we need to reregister our waker and go to sleep
                 Err(message) => {
                     if let Some(cx) = cx {
@@ -228,10 +250,10 @@ impl<M: RawMutex, T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usi
         })
     }
 
-    fn unregister_subscriber(&self, subscriber_next_message_id: u64) {
+    fn unregister_subscriber(&self, subscriber: usize) {
         self.inner.lock(|s| {
             let mut s = s.borrow_mut();
-            s.unregister_subscriber(subscriber_next_message_id)
+            s.unregister_subscriber(subscriber)
         })
     }
 
@@ -241,6 +263,27 @@ impl<M: RawMutex, T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usi
             s.unregister_publisher()
         })
     }
+
+    fn close(&self) {
+        PubSubChannel::close(self)
+    }
+
+    fn is_closed(&self) -> bool {
+        PubSubChannel::is_closed(self)
+    }
+}
+
+/// Internal state of a subscriber of the PubSub channel
+struct SubscriberState<T> {
+    /// The message id of the next message the subscriber is yet to receive.
+    /// This can be older than the oldest message in the queue if the messages in between were
+    /// read by everyone interested in them.
+    next_message_id: u64,
+    /// The amount of messages the subscriber missed, to be reported as lag
+    missed: u64,
+    filter: Filter<T>,
+    policy: LagPolicy,
+    waker: WakerRegistration,
 }
 
 /// Internal state for the PubSub channel
@@ -251,99 +294,210 @@ struct PubSubState<T: Clone, const CAP: usize, const SUBS: usize, const PUBS: us
     /// Don't worry, we won't run out.
     /// If a million messages were published every second, then the ID's would run out in about 584942 years.
     next_message_id: u64,
-    /// Collection of wakers for Subscribers that are waiting.  
-    subscriber_wakers: MultiWakerRegistration<SUBS>,
+    /// The subscriber slots. A subscriber refers to its state by the index of its slot.
+    subscribers: [Option<SubscriberState<T>>; SUBS],
     /// Collection of wakers for Publishers that are waiting.  
     publisher_wakers: MultiWakerRegistration<PUBS>,
-    /// The amount of subscribers that are active
-    subscriber_count: usize,
     /// The amount of publishers that are active
     publisher_count: usize,
+    /// Whether the channel was closed
+    closed: bool,
 }
 
 impl<T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usize> PubSubState<T, CAP, SUBS, PUBS> {
+    const NO_SUBSCRIBER: Option<SubscriberState<T>> = None;
+
     /// Create a new internal channel state
     const fn new() -> Self {
         Self {
             queue: Deque::new(),
             next_message_id: 0,
-            subscriber_wakers: MultiWakerRegistration::new(),
+            subscribers: [Self::NO_SUBSCRIBER; SUBS],
             publisher_wakers: MultiWakerRegistration::new(),
-            subscriber_count: 0,
             publisher_count: 0,
+            closed: false,
         }
     }
 
+    fn subscribe(&mut self, filter: Filter<T>, policy: LagPolicy) -> Result<usize, Error> {
+        let slot = self
+            .subscribers
+            .iter()
+            .position(Option::is_none)
+            .ok_or(Error::MaximumSubscribersReached)?;
+
+        self.subscribers[slot] = Some(SubscriberState {
+            next_message_id: self.next_message_id,
+            missed: 0,
+            filter,
+            policy,
+            waker: WakerRegistration::new(),
+        });
+        Ok(slot)
+    }
+
+    fn subscriber_mut(&mut self, subscriber: usize) -> &mut SubscriberState<T> {
+        unwrap!(self.subscribers[subscriber].as_mut())
+    }
+
+    /// The id of the oldest message in the queue
+    fn start_id(&self) -> u64 {
+        self.next_message_id - self.queue.len() as u64
+    }
+
     fn try_publish(&mut self, message: T) -> Result<(), T> {
-        if self.subscriber_count == 0 {
+        self.publish(message, false)
+    }
+
+    fn publish_immediate(&mut self, message: T) {
+        // This only fails when the channel is closed, otherwise the oldest message is dropped if required
+        self.publish(message, true).ok();
+    }
+
+    /// Publish a message. If the queue is full, the oldest message is dropped when `force` is set,
+    /// otherwise the lag policies of the subscribers that still have to read it decide.
+    fn publish(&mut self, message: T, force: bool) -> Result<(), T> {
+        if self.closed {
+            // Nobody will receive the message anymore
+            return Err(message);
+        }
+
+        let readers = self
+            .subscribers
+            .iter()
+            .flatten()
+            .filter(|sub| sub.filter.matches(&message))
+            .count();
+
+        if readers == 0 {
             // We don't need to publish anything because there is no one to receive it
             return Ok(());
         }
 
         if self.queue.is_full() {
-            return Err(message);
+            let start_id = self.start_id();
+            // We just checked the queue is full, so it's not empty
+            let (oldest, _) = self.queue.front().unwrap();
+            let mut block = false;
+            let mut drop_newest = false;
+            for sub in self.subscribers.iter().flatten() {
+                if sub.next_message_id <= start_id && sub.filter.matches(oldest) {
+                    match sub.policy {
+                        LagPolicy::Block => block = true,
+                        LagPolicy::DropNewest => drop_newest = true,
+                        LagPolicy::DropOldest => {}
+                    }
+                }
+            }
+
+            if !force && block {
+                return Err(message);
+            } else if !force && drop_newest {
+                // The new message doesn't fit, so everyone interested in it misses it
+                for sub in self.subscribers.iter_mut().flatten() {
+                    if sub.filter.matches(&message) {
+                        sub.missed += 1;
+                        sub.waker.wake();
+                    }
+                }
+                return Ok(());
+            }
+
+            self.drop_oldest();
         }
-        // We just did a check for this
-        self.queue.push_back((message, self.subscriber_count)).ok().unwrap();
 
+        // We just made sure there is space
+        self.queue.push_back((message, readers)).ok().unwrap();
         self.next_message_id += 1;
 
-        // Wake all of the subscribers
-        self.subscriber_wakers.wake();
+        // Wake the subscribers that are interested in the message
+        let (message, _) = self.queue.back().unwrap();
+        for sub in self.subscribers.iter_mut().flatten() {
+            if sub.filter.matches(message) {
+                sub.waker.wake();
+            }
+        }
 
         Ok(())
     }
 
-    fn publish_immediate(&mut self, message: T) {
-        // Make space in the queue if required
-        if self.queue.is_full() {
-            self.queue.pop_front();
+    /// Drop the oldest message, making the subscribers that still had to read it lag.
+    fn drop_oldest(&mut self) {
+        let start_id = self.start_id();
+        let Some((oldest, _)) = self.queue.pop_front() else {
+            return;
+        };
+
+        for sub in self.subscribers.iter_mut().flatten() {
+            if sub.next_message_id <= start_id {
+                sub.next_message_id = start_id + 1;
+                if sub.filter.matches(&oldest) {
+                    sub.missed += 1;
+                }
+            }
         }
-
-        // This will succeed because we made sure there is space
-        self.try_publish(message).ok().unwrap();
     }
 
-    fn get_message(&mut self, message_id: u64) -> Option<WaitResult<T>> {
-        let start_id = self.next_message_id - self.queue.len() as u64;
+    /// Pop the messages at the front of the queue that all subscribers have read
+    fn pop_read_messages(&mut self) {
+        let mut popped = false;
+        while let Some((_, 0)) = self.queue.front() {
+            self.queue.pop_front();
+            popped = true;
+        }
 
-        if message_id < start_id {
-            return Some(WaitResult::Lagged(start_id - message_id));
+        if popped {
+            self.publisher_wakers.wake();
         }
+    }
 
-        let current_message_index = (message_id - start_id) as usize;
+    fn get_message(&mut self, subscriber: usize) -> Option<WaitResult<T>> {
+        let start_id = self.start_id();
+        let sub = unwrap!(self.subscribers[subscriber].as_mut());
 
-        if current_message_index >= self.queue.len() {
-            return None;
+        if sub.missed > 0 {
+            return Some(WaitResult::Lagged(core::mem::replace(&mut sub.missed, 0)));
         }
 
-        // We've checked that the index is valid
-        let queue_item = self.queue.iter_mut().nth(current_message_index).unwrap();
+        // Messages older than the queue were not of interest to this subscriber
+        sub.next_message_id = sub.next_message_id.max(start_id);
+
+        // Skip the messages the subscriber is not interested in
+        let message = self
+            .queue
+            .iter_mut()
+            .skip((sub.next_message_id - start_id) as usize)
+            .find_map(|(message, readers)| {
+                sub.next_message_id += 1;
+                if sub.filter.matches(message) {
+                    // We're reading this item, so decrement the counter
+                    *readers -= 1;
+                    Some(message.clone())
+                } else {
+                    None
+                }
+            });
 
-        // We're reading this item, so decrement the counter
-        queue_item.1 -= 1;
-        let message = queue_item.0.clone();
+        self.pop_read_messages();
 
-        if current_message_index == 0 && queue_item.1 == 0 {
-            self.queue.pop_front();
-            self.publisher_wakers.wake();
+        match message {
+            Some(message) => Some(WaitResult::Message(message)),
+            None if self.closed => Some(WaitResult::Closed),
+            None => None,
         }
-
-        Some(WaitResult::Message(message))
     }
 
-    fn register_subscriber_waker(&mut self, waker: &Waker) {
-        match self.subscriber_wakers.register(waker) {
-            Ok(()) => {}
-            Err(_) => {
-                // All waker slots were full. This can only happen when there was a subscriber that now has dropped.
-                // We need to throw it away. It's a bit inefficient, but we can wake everything.
-                // Any future that is still active will simply reregister.
-                // This won't happen a lot, so it's ok.
-                self.subscriber_wakers.wake();
-                self.subscriber_wakers.register(waker).unwrap();
-            }
-        }
+    fn available(&self, subscriber: usize) -> u64 {
+        let start_id = self.start_id();
+        let sub = unwrap!(self.subscribers[subscriber].as_ref());
+
+        let queued = self
+            .queue
+            .iter()
+            .skip(sub.next_message_id.saturating_sub(start_id) as usize)
+            .filter(|(message, _)| sub.filter.matches(message))
+            .count();
+        sub.missed + queued as u64
     }
 
     fn register_publisher_waker(&mut self, waker: &Waker) {
@@ -360,23 +514,33 @@ impl<T: Clone, const CAP: usize, const SUBS: usize, const PUBS: usize> PubSubSta
         }
     }
 
-    fn unregister_subscriber(&mut self, subscriber_next_message_id: u64) {
-        self.subscriber_count -= 1;
+    fn unregister_subscriber(&mut self, subscriber: usize) {
+        let start_id = self.start_id();
+        let sub = unwrap!(self.subscribers[subscriber].take());
 
         // All messages that haven't been read yet by this subscriber must have their counter decremented
-        let start_id = self.next_message_id - self.queue.len() as u64;
-        if subscriber_next_message_id >= start_id {
-            let current_message_index = (subscriber_next_message_id - start_id) as usize;
-            self.queue
-                .iter_mut()
-                .skip(current_message_index)
-                .for_each(|(_, counter)| *counter -= 1);
-        }
+        self.queue
+            .iter_mut()
+            .skip(sub.next_message_id.saturating_sub(start_id) as usize)
+            .filter(|(message, _)| sub.filter.matches(message))
+            .for_each(|(_, readers)| *readers -= 1);
+
+        self.pop_read_messages();
     }
 
     fn unregister_publisher(&mut self) {
         self.publisher_count -= 1;
     }
+
+    fn close(&mut self) {
+        self.closed = true;
+
+        for sub in self.subscribers.iter_mut().flatten() {
+            sub.waker.wake();
+        }
+        // Waiting publishers can give up their message
+        self.publisher_wakers.wake();
+    }
 }
 
 /// Error type for the [PubSubChannel]
@@ -394,18 +558,20 @@ pub enum Error {
 /// 'Middle level' behaviour of the pubsub channel.
 /// This trait is used so that Sub and Pub can be generic over the channel.
 pub trait PubSubBehavior<T> {
-    /// Try to get a message from the queue with the given message id.
+    /// Try to get the next message for the subscriber in the given slot.
     ///
-    /// If the message is not yet present and a context is given, then its waker is registered in the subsriber wakers.
-    fn get_message_with_context(&self, next_message_id: &mut u64, cx: Option<&mut Context<'_>>) -> Poll<WaitResult<T>>;
+    /// If there is no message yet and a context is given, then its waker is registered for the subscriber.
+    fn get_message_with_context(&self, subscriber: usize, cx: Option<&mut Context<'_>>) -> Poll<WaitResult<T>>;
 
-    /// Get the amount of messages that are between the given the next_message_id and the most recent message.
-    /// This is not necessarily the amount of messages a subscriber can still received as it may have lagged.
-    fn available(&self, next_message_id: u64) -> u64;
+    /// Get the amount of messages the subscriber in the given slot has not received yet.
+    /// This includes the messages it lagged behind on, so it's not necessarily the amount of messages
+    /// the subscriber can still receive.
+    fn available(&self, subscriber: usize) -> u64;
 
     /// Try to publish a message to the queue.
     ///
-    /// If the queue is full and a context is given, then its waker is registered in the publisher wakers.
+    /// The message is handed back if the queue is full or the channel is closed. If the queue is full
+    /// and a context is given, then its waker is registered in the publisher wakers.
     fn publish_with_context(&self, message: T, cx: Option<&mut Context<'_>>) -> Result<(), T>;
 
     /// Publish a message immediately
@@ -414,11 +580,17 @@ pub trait PubSubBehavior<T> {
     /// The amount of messages that can still be published without having to wait or without having to lag the subscribers
     fn space(&self) -> usize;
 
-    /// Let the channel know that a subscriber has dropped
-    fn unregister_subscriber(&self, subscriber_next_message_id: u64);
+    /// Let the channel know that the subscriber in the given slot has dropped
+    fn unregister_subscriber(&self, subscriber: usize);
 
     /// Let the channel know that a publisher has dropped
     fn unregister_publisher(&self);
+
+    /// Close the channel
+    fn close(&self);
+
+    /// Returns whether the channel is closed
+    fn is_closed(&self) -> bool;
 }
 
 /// The result of the subscriber wait procedure
@@ -430,12 +602,75 @@ pub enum WaitResult<T> {
     Lagged(u64),
     /// A message was received
     Message(T),
+    /// The channel was closed and all messages have been received
+    Closed,
+}
+
+/// Selects the messages a subscriber receives.
+///
+/// Messages that don't match the filter of a subscriber are skipped by it. If no subscriber is
+/// interested in a message, it is not queued at all.
+pub enum Filter<T> {
+    /// Receive all messages.
+    All,
+    /// Receive the messages for which the predicate returns `true`.
+    Predicate(fn(&T) -> bool),
+    /// Receive the messages for which the topic bits returned by `topics` intersect with `mask`.
+    Topics {
+        /// Returns the topics a message belongs to, as a bit set.
+        topics: fn(&T) -> u32,
+        /// The topics the subscriber is interested in.
+        mask: u32,
+    },
+}
+
+impl<T> Filter<T> {
+    fn matches(&self, message: &T) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Predicate(predicate) => predicate(message),
+            Filter::Topics { topics, mask } => topics(message) & mask != 0,
+        }
+    }
+}
+
+impl<T> Clone for Filter<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Filter<T> {}
+
+/// What happens when a message is published with [Pub::publish()] or [Pub::try_publish()] while the
+/// queue is full.
+///
+/// The policies of the subscribers that have not yet received the oldest message in the queue
+/// decide: if any of them uses [`Block`](LagPolicy::Block), the publisher waits. Otherwise, if any
+/// of them uses [`DropNewest`](LagPolicy::DropNewest), the new message is dropped. Otherwise, the
+/// oldest message is dropped. Subscribers that miss a message they are interested in receive a
+/// [`WaitResult::Lagged`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LagPolicy {
+    /// Make publishers wait until the subscriber has received the oldest message.
+    Block,
+    /// Drop the oldest message.
+    DropOldest,
+    /// Keep the queued messages and drop the new message.
+    DropNewest,
 }
 
 #[cfg(test)]
 mod tests {
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+
     use super::*;
-    use crate::blocking_mutex::raw::NoopRawMutex;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
 
     #[futures_test::test]
     async fn dyn_pub_sub_works() {
@@ -445,7 +680,7 @@ mod tests {
         let mut sub1 = channel.dyn_subscriber().unwrap();
         let pub0 = channel.dyn_publisher().unwrap();
 
-        pub0.publish(42).await;
+        pub0.publish(42).await.unwrap();
 
         assert_eq!(sub0.next_message().await, WaitResult::Message(42));
         assert_eq!(sub1.next_message().await, WaitResult::Message(42));
@@ -462,7 +697,7 @@ mod tests {
         let mut sub1 = channel.subscriber().unwrap();
         let pub0 = channel.publisher().unwrap();
 
-        pub0.publish(42).await;
+        pub0.publish(42).await.unwrap();
 
         assert_eq!(sub0.next_message().await, WaitResult::Message(42));
         assert_eq!(sub1.next_message().await, WaitResult::Message(42));
@@ -569,7 +804,7 @@ mod tests {
         assert_eq!(sub0.available(), 0);
         assert_eq!(sub1.available(), 0);
 
-        pub0.publish(42).await;
+        pub0.publish(42).await.unwrap();
 
         assert_eq!(sub0.available(), 1);
         assert_eq!(sub1.available(), 1);
@@ -578,7 +813,7 @@ mod tests {
 
         assert_eq!(sub1.available(), 0);
 
-        pub0.publish(42).await;
+        pub0.publish(42).await.unwrap();
 
         assert_eq!(sub0.available(), 2);
         assert_eq!(sub1.available(), 1);
@@ -594,11 +829,11 @@ mod tests {
 
         assert_eq!(pub0.space(), 4);
 
-        pub0.publish(42).await;
+        pub0.publish(42).await.unwrap();
 
         assert_eq!(pub0.space(), 3);
 
-        pub0.publish(42).await;
+        pub0.publish(42).await.unwrap();
 
         assert_eq!(pub0.space(), 2);
 
@@ -612,4 +847,212 @@ mod tests {
         sub1.next_message().await;
         assert_eq!(pub0.space(), 4);
     }
+
+    #[futures_test::test]
+    async fn filtered_messages_are_skipped() {
+        let channel = PubSubChannel::<NoopRawMutex, u32, 4, 4, 4>::new();
+
+        let mut even = channel
+            .subscriber_with(Filter::Predicate(|m| m % 2 == 0), LagPolicy::Block)
+            .unwrap();
+        let pub0 = channel.publisher().unwrap();
+
+        // Nobody is interested in odd messages, so they are not queued
+        pub0.publish(1).await.unwrap();
+        assert_eq!(pub0.space(), 4);
+
+        pub0.publish(2).await.unwrap();
+        pub0.publish(3).await.unwrap();
+        pub0.publish(4).await.unwrap();
+        assert_eq!(even.available(), 2);
+
+        assert_eq!(even.next_message().await, WaitResult::Message(2));
+        assert_eq!(even.next_message().await, WaitResult::Message(4));
+        assert_eq!(even.try_next_message(), None);
+        assert_eq!(pub0.space(), 4);
+    }
+
+    #[futures_test::test]
+    async fn filtered_subscriber_behind_the_queue() {
+        let channel = PubSubChannel::<NoopRawMutex, u32, 4, 4, 4>::new();
+
+        let mut even = channel
+            .subscriber_with(Filter::Predicate(|m| m % 2 == 0), LagPolicy::Block)
+            .unwrap();
+        let mut all = channel.subscriber().unwrap();
+        let pub0 = channel.publisher().unwrap();
+
+        pub0.publish(1).await.unwrap();
+        pub0.publish(2).await.unwrap();
+
+        // Reading 1 pops it from the queue, leaving `even` behind the oldest queued message
+        assert_eq!(all.next_message().await, WaitResult::Message(1));
+        assert_eq!(even.next_message().await, WaitResult::Message(2));
+        assert_eq!(even.try_next_message(), None);
+        assert_eq!(even.available(), 0);
+
+        assert_eq!(all.next_message().await, WaitResult::Message(2));
+        assert_eq!(pub0.space(), 4);
+    }
+
+    #[futures_test::test]
+    async fn filtered_messages_dont_count_against_capacity() {
+        let channel = PubSubChannel::<NoopRawMutex, u32, 4, 4, 4>::new();
+
+        let mut low = channel
+            .subscriber_with(
+                Filter::Topics {
+                    topics: |m| 1 << (m / 10),
+                    mask: 0b01,
+                },
+                LagPolicy::Block,
+            )
+            .unwrap();
+        let mut high = channel
+            .subscriber_with(
+                Filter::Topics {
+                    topics: |m| 1 << (m / 10),
+                    mask: 0b10,
+                },
+                LagPolicy::Block,
+            )
+            .unwrap();
+        let pub0 = channel.publisher().unwrap();
+
+        assert_eq!(pub0.try_publish(1), Ok(()));
+        assert_eq!(pub0.try_publish(11), Ok(()));
+        assert_eq!(pub0.try_publish(2), Ok(()));
+        assert_eq!(pub0.try_publish(12), Ok(()));
+        assert_eq!(pub0.try_publish(3), Err(3));
+
+        // Reading its messages frees up the queue, even though the other subscriber didn't read anything
+        assert_eq!(low.try_next_message(), Some(WaitResult::Message(1)));
+        assert_eq!(pub0.space(), 1);
+        assert_eq!(low.try_next_message(), Some(WaitResult::Message(2)));
+        assert_eq!(low.try_next_message(), None);
+        assert_eq!(pub0.space(), 1);
+
+        assert_eq!(high.try_next_message(), Some(WaitResult::Message(11)));
+        assert_eq!(pub0.space(), 3);
+
+        drop(high);
+        assert_eq!(pub0.space(), 4);
+    }
+
+    #[futures_test::test]
+    async fn lag_policies() {
+        let channel = PubSubChannel::<NoopRawMutex, u32, 2, 4, 4>::new();
+        let pub0 = channel.publisher().unwrap();
+
+        {
+            let mut sub0 = channel.subscriber_with(Filter::All, LagPolicy::DropOldest).unwrap();
+            pub0.publish(1).await.unwrap();
+            pub0.publish(2).await.unwrap();
+            pub0.publish(3).await.unwrap();
+
+            assert_eq!(sub0.available(), 3);
+            assert_eq!(sub0.try_next_message(), Some(WaitResult::Lagged(1)));
+            assert_eq!(sub0.try_next_message(), Some(WaitResult::Message(2)));
+            assert_eq!(sub0.try_next_message(), Some(WaitResult::Message(3)));
+        }
+
+        {
+            let mut sub0 = channel.subscriber_with(Filter::All, LagPolicy::DropNewest).unwrap();
+            pub0.publish(1).await.unwrap();
+            pub0.publish(2).await.unwrap();
+            pub0.publish(3).await.unwrap();
+
+            assert_eq!(sub0.try_next_message(), Some(WaitResult::Lagged(1)));
+            assert_eq!(sub0.try_next_message(), Some(WaitResult::Message(1)));
+            assert_eq!(sub0.try_next_message(), Some(WaitResult::Message(2)));
+            assert_eq!(sub0.try_next_message(), None);
+        }
+
+        {
+            // The subscriber holding up the queue decides
+            let mut blocking = channel.subscriber().unwrap();
+            let mut dropping = channel.subscriber_with(Filter::All, LagPolicy::DropOldest).unwrap();
+            pub0.publish(1).await.unwrap();
+            pub0.publish(2).await.unwrap();
+            assert_eq!(blocking.try_next_message(), Some(WaitResult::Message(1)));
+            assert_eq!(pub0.try_publish(3), Ok(()));
+            assert_eq!(pub0.try_publish(4), Err(4));
+
+            assert_eq!(dropping.try_next_message(), Some(WaitResult::Lagged(1)));
+            assert_eq!(dropping.try_next_message(), Some(WaitResult::Message(2)));
+            assert_eq!(blocking.try_next_message(), Some(WaitResult::Message(2)));
+        }
+    }
+
+    #[futures_test::test]
+    async fn close() {
+        let channel = PubSubChannel::<NoopRawMutex, u32, 4, 4, 4>::new();
+
+        let mut sub0 = channel.subscriber().unwrap();
+        let pub0 = channel.publisher().unwrap();
+
+        pub0.publish(42).await.unwrap();
+        pub0.close();
+        assert!(channel.is_closed());
+        assert!(sub0.is_closed());
+
+        // Messages published after closing are handed back
+        assert_eq!(pub0.publish(43).await, Err(43));
+        assert_eq!(pub0.try_publish(44), Err(44));
+        pub0.publish_immediate(45);
+
+        assert_eq!(sub0.next_message().await, WaitResult::Message(42));
+        assert_eq!(sub0.next_message().await, WaitResult::Closed);
+        assert_eq!(sub0.try_next_message(), Some(WaitResult::Closed));
+        assert_eq!(sub0.try_next_message_pure(), None);
+    }
+
+    #[futures_test::test]
+    async fn close_releases_waiting_publisher() {
+        let channel = PubSubChannel::<NoopRawMutex, u32, 1, 4, 4>::new();
+
+        let _sub0 = channel.subscriber().unwrap();
+        let pub0 = channel.publisher().unwrap();
+        pub0.publish(1).await.unwrap();
+
+        let mut publish = pub0.publish(2);
+        assert!(futures_util::poll!(&mut publish).is_pending());
+
+        channel.close();
+        assert_eq!(publish.await, Err(2));
+    }
+
+    #[futures_test::test]
+    async fn stream_ends_on_close() {
+        use futures_util::StreamExt;
+
+        let channel = PubSubChannel::<NoopRawMutex, u32, 4, 4, 4>::new();
+
+        let mut sub0 = channel.subscriber().unwrap();
+        let pub0 = channel.publisher().unwrap();
+
+        pub0.publish(1).await.unwrap();
+        pub0.publish(2).await.unwrap();
+        pub0.publish(3).await.unwrap();
+        channel.close();
+
+        assert_eq!(sub0.by_ref().collect::<heapless::Vec<u32, 4>>().await, [1, 2, 3]);
+    }
+
+    #[futures_test::test]
+    async fn close_wakes_subscriber() {
+        let executor = ThreadPool::new().unwrap();
+
+        static CHANNEL: PubSubChannel<CriticalSectionRawMutex, u32, 4, 4, 4> = PubSubChannel::new();
+
+        let mut sub0 = CHANNEL.subscriber().unwrap();
+        executor
+            .spawn(async {
+                Delay::new(Duration::from_millis(100)).await;
+                CHANNEL.close();
+            })
+            .unwrap();
+
+        assert_eq!(sub0.next_message().await, WaitResult::Closed);
+    }
 }
@@ -26,11 +26,15 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Pub<'a, PSB, T> {
 
     /// Publish a message right now even when the queue is full.
     /// This may cause a subscriber to miss an older message.
+    ///
+    /// If the channel is closed, the message is dropped.
     pub fn publish_immediate(&self, message: T) {
         self.channel.publish_immediate(message)
     }
 
     /// Publish a message. But if the message queue is full, wait for all subscribers to have read the last message
+    ///
+    /// If the channel is closed, the message is handed back as an error.
     pub fn publish<'s>(&'s self, message: T) -> PublisherWaitFuture<'s, 'a, PSB, T> {
         PublisherWaitFuture {
             message: Some(message),
@@ -39,6 +43,8 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Pub<'a, PSB, T> {
     }
 
     /// Publish a message if there is space in the message queue
+    ///
+    /// The message is handed back as an error if the queue is full or the channel is closed.
     pub fn try_publish(&self, message: T) -> Result<(), T> {
         self.channel.publish_with_context(message, None)
     }
@@ -50,6 +56,15 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Pub<'a, PSB, T> {
     pub fn space(&self) -> usize {
         self.channel.space()
     }
+
+    /// Close the channel. Subscribers receive the remaining messages, after which they are notified
+    /// that the channel is closed.
+    ///
+    /// Publishing after closing fails, handing the message back, except for `publish_immediate`
+    /// which drops it.
+    pub fn close(&self) {
+        self.channel.close()
+    }
 }
 
 impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Drop for Pub<'a, PSB, T> {
@@ -115,11 +130,15 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> ImmediatePub<'a, PSB, T> {
     }
     /// Publish the message right now even when the queue is full.
     /// This may cause a subscriber to miss an older message.
+    ///
+    /// If the channel is closed, the message is dropped.
     pub fn publish_immediate(&self, message: T) {
         self.channel.publish_immediate(message)
     }
 
     /// Publish a message if there is space in the message queue
+    ///
+    /// The message is handed back as an error if the queue is full or the channel is closed.
     pub fn try_publish(&self, message: T) -> Result<(), T> {
         self.channel.publish_with_context(message, None)
     }
@@ -131,6 +150,15 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> ImmediatePub<'a, PSB, T> {
     pub fn space(&self) -> usize {
         self.channel.space()
     }
+
+    /// Close the channel. Subscribers receive the remaining messages, after which they are notified
+    /// that the channel is closed.
+    ///
+    /// Publishing after closing fails, handing the message back, except for `publish_immediate`
+    /// which drops it.
+    pub fn close(&self) {
+        self.channel.close()
+    }
 }
 
 /// An immediate publisher that holds a dynamic reference to the channel
@@ -182,12 +210,13 @@ pub struct PublisherWaitFuture<'s, 'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone
 }
 
 impl<'s, 'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Future for PublisherWaitFuture<'s, 'a, PSB, T> {
-    type Output = ();
+    type Output = Result<(), T>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let message = self.message.take().unwrap();
         match self.publisher.channel.publish_with_context(message, Some(cx)) {
-            Ok(()) => Poll::Ready(()),
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(message) if self.publisher.channel.is_closed() => Poll::Ready(Err(message)),
             Err(message) => {
                 self.message = Some(message);
                 Poll::Pending
@@ -11,17 +11,17 @@ use crate::blocking_mutex::raw::RawMutex;
 
 /// A subscriber to a channel
 pub struct Sub<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> {
-    /// The message id of the next message we are yet to receive
-    next_message_id: u64,
+    /// The slot of this subscriber in the channel
+    slot: usize,
     /// The channel we are a subscriber to
     channel: &'a PSB,
     _phantom: PhantomData<T>,
 }
 
 impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Sub<'a, PSB, T> {
-    pub(super) fn new(next_message_id: u64, channel: &'a PSB) -> Self {
+    pub(super) fn new(slot: usize, channel: &'a PSB) -> Self {
         Self {
-            next_message_id,
+            slot,
             channel,
             _phantom: Default::default(),
         }
@@ -33,11 +33,16 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Sub<'a, PSB, T> {
     }
 
     /// Wait for a published message (ignoring lag results)
+    ///
+    /// Don't use this on a channel that can be closed: once the channel is closed and all messages
+    /// have been received, this never returns. Use [`next_message`](Self::next_message) or the
+    /// `Stream` implementation instead, which report the channel being closed.
     pub async fn next_message_pure(&mut self) -> T {
         loop {
             match self.next_message().await {
                 WaitResult::Lagged(_) => continue,
                 WaitResult::Message(message) => break message,
+                WaitResult::Closed => core::future::pending().await,
             }
         }
     }
@@ -46,7 +51,7 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Sub<'a, PSB, T> {
     ///
     /// This function does not peek. The message is received if there is one.
     pub fn try_next_message(&mut self) -> Option<WaitResult<T>> {
-        match self.channel.get_message_with_context(&mut self.next_message_id, None) {
+        match self.channel.get_message_with_context(self.slot, None) {
             Poll::Ready(result) => Some(result),
             Poll::Pending => None,
         }
@@ -60,20 +65,25 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Sub<'a, PSB, T> {
             match self.try_next_message() {
                 Some(WaitResult::Lagged(_)) => continue,
                 Some(WaitResult::Message(message)) => break Some(message),
-                None => break None,
+                Some(WaitResult::Closed) | None => break None,
             }
         }
     }
 
     /// The amount of messages this subscriber hasn't received yet
     pub fn available(&self) -> u64 {
-        self.channel.available(self.next_message_id)
+        self.channel.available(self.slot)
+    }
+
+    /// Returns whether the channel is closed. There may still be messages left to receive.
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
     }
 }
 
 impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Drop for Sub<'a, PSB, T> {
     fn drop(&mut self) {
-        self.channel.unregister_subscriber(self.next_message_id)
+        self.channel.unregister_subscriber(self.slot)
     }
 }
 
@@ -81,15 +91,15 @@ impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Unpin for Sub<'a, PSB, T> {}
 
 /// Warning: The stream implementation ignores lag results and returns all messages.
 /// This might miss some messages without you knowing it.
+///
+/// The stream ends when the channel is closed and all messages have been received.
 impl<'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> futures_util::Stream for Sub<'a, PSB, T> {
     type Item = T;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self
-            .channel
-            .get_message_with_context(&mut self.next_message_id, Some(cx))
-        {
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.channel.get_message_with_context(self.slot, Some(cx)) {
             Poll::Ready(WaitResult::Message(message)) => Poll::Ready(Some(message)),
+            Poll::Ready(WaitResult::Closed) => Poll::Ready(None),
             Poll::Ready(WaitResult::Lagged(_)) => {
                 cx.waker().wake_by_ref();
                 Poll::Pending
@@ -148,10 +158,10 @@ pub struct SubscriberWaitFuture<'s, 'a, PSB: PubSubBehavior<T> + ?Sized, T: Clon
 impl<'s, 'a, PSB: PubSubBehavior<T> + ?Sized, T: Clone> Future for SubscriberWaitFuture<'s, 'a, PSB, T> {
     type Output = WaitResult<T>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         self.subscriber
             .channel
-            .get_message_with_context(&mut self.subscriber.next_message_id, Some(cx))
+            .get_message_with_context(self.subscriber.slot, Some(cx))
     }
 }
 
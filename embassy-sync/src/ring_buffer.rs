@@ -1,5 +1,7 @@
+use core::ops::Range;
+
+/// Positions of the data in a ring buffer of `N` bytes. The bytes themselves are stored by the user.
 pub struct RingBuffer<const N: usize> {
-    buf: [u8; N],
     start: usize,
     end: usize,
     empty: bool,
@@ -8,27 +10,27 @@ pub struct RingBuffer<const N: usize> {
 impl<const N: usize> RingBuffer<N> {
     pub const fn new() -> Self {
         Self {
-            buf: [0; N],
             start: 0,
             end: 0,
             empty: true,
         }
     }
 
-    pub fn push_buf(&mut self) -> &mut [u8] {
+    /// Free space to write to, up to the end of the buffer.
+    pub fn push_buf(&self) -> Range<usize> {
         if self.start == self.end && !self.empty {
             trace!("  ringbuf: push_buf empty");
-            return &mut self.buf[..0];
+            return self.end..self.end;
         }
 
         let n = if self.start <= self.end {
-            self.buf.len() - self.end
+            N - self.end
         } else {
             self.start - self.end
         };
 
         trace!("  ringbuf: push_buf {:?}..{:?}", self.end, self.end + n);
-        &mut self.buf[self.end..self.end + n]
+        self.end..self.end + n
     }
 
     pub fn push(&mut self, n: usize) {
@@ -41,20 +43,21 @@ impl<const N: usize> RingBuffer<N> {
         self.empty = false;
     }
 
-    pub fn pop_buf(&mut self) -> &mut [u8] {
+    /// Data to read from, up to the end of the buffer.
+    pub fn pop_buf(&self) -> Range<usize> {
         if self.empty {
             trace!("  ringbuf: pop_buf empty");
-            return &mut self.buf[..0];
+            return self.start..self.start;
         }
 
         let n = if self.end <= self.start {
-            self.buf.len() - self.start
+            N - self.start
         } else {
             self.end - self.start
         };
 
         trace!("  ringbuf: pop_buf {:?}..{:?}", self.start, self.start + n);
-        &mut self.buf[self.start..self.start + n]
+        self.start..self.start + n
     }
 
     pub fn pop(&mut self, n: usize) {
@@ -92,9 +95,18 @@ impl<const N: usize> RingBuffer<N> {
         self.empty = true;
     }
 
+    /// Drop the data after the first `len` bytes, without moving the start of the data.
+    ///
+    /// The first `len` bytes must be contiguous, i.e. `len` is at most the length of `pop_buf`.
+    pub fn truncate(&mut self, len: usize) {
+        assert!(len <= self.pop_buf().len());
+        self.end = self.wrap(self.start + len);
+        self.empty = len == 0;
+    }
+
     fn wrap(&self, n: usize) -> usize {
-        assert!(n <= self.buf.len());
-        if n == self.buf.len() {
+        assert!(n <= N);
+        if n == N {
             0
         } else {
             n
@@ -109,38 +121,37 @@ mod tests {
     #[test]
     fn push_pop() {
         let mut rb: RingBuffer<4> = RingBuffer::new();
-        let buf = rb.push_buf();
-        assert_eq!(4, buf.len());
-        buf[0] = 1;
-        buf[1] = 2;
-        buf[2] = 3;
-        buf[3] = 4;
+        assert_eq!(0..4, rb.push_buf());
         rb.push(4);
 
-        let buf = rb.pop_buf();
-        assert_eq!(4, buf.len());
-        assert_eq!(1, buf[0]);
+        assert_eq!(0..4, rb.pop_buf());
         rb.pop(1);
 
-        let buf = rb.pop_buf();
-        assert_eq!(3, buf.len());
-        assert_eq!(2, buf[0]);
+        assert_eq!(1..4, rb.pop_buf());
         rb.pop(1);
 
-        let buf = rb.pop_buf();
-        assert_eq!(2, buf.len());
-        assert_eq!(3, buf[0]);
+        assert_eq!(2..4, rb.pop_buf());
         rb.pop(1);
 
-        let buf = rb.pop_buf();
-        assert_eq!(1, buf.len());
-        assert_eq!(4, buf[0]);
+        assert_eq!(3..4, rb.pop_buf());
         rb.pop(1);
 
-        let buf = rb.pop_buf();
-        assert_eq!(0, buf.len());
+        assert_eq!(0, rb.pop_buf().len());
+
+        assert_eq!(0..4, rb.push_buf());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut rb: RingBuffer<4> = RingBuffer::new();
+        rb.push(3);
+        rb.pop(1);
+        rb.truncate(1);
+        assert_eq!(1..2, rb.pop_buf());
+        assert_eq!(2..4, rb.push_buf());
 
-        let buf = rb.push_buf();
-        assert_eq!(4, buf.len());
+        rb.truncate(0);
+        assert!(rb.is_empty());
+        assert_eq!(1..1, rb.pop_buf());
     }
 }
@@ -0,0 +1,680 @@
+//! Async read-write lock.
+//!
+//! This module provides a read-write lock that can be used to synchronize data between asynchronous tasks.
+use core::cell::{RefCell, UnsafeCell};
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::task::Poll;
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex as BlockingMutex;
+use crate::waitqueue::{WakerList, WakerListEntry};
+
+/// Error returned by [`RwLock::try_read`] and [`RwLock::try_write`]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TryLockError;
+
+struct State {
+    /// Number of active readers.
+    readers: usize,
+    /// Whether a writer holds the lock.
+    writer: bool,
+    /// Number of writers waiting for the lock. While non-zero, new readers are held back.
+    writers_waiting: usize,
+    wakers: WakerList,
+}
+
+impl State {
+    fn can_read(&self) -> bool {
+        !self.writer && self.writers_waiting == 0
+    }
+
+    fn can_write(&self) -> bool {
+        !self.writer && self.readers == 0
+    }
+}
+
+/// Async read-write lock.
+///
+/// The lock allows any number of readers or at most one writer at a time. Writers are
+/// preferred: once a writer is waiting, new readers wait until it has acquired and
+/// released the lock, so a steady stream of readers cannot starve a writer.
+///
+/// The lock is generic over a blocking [`RawMutex`](crate::blocking_mutex::raw::RawMutex).
+/// The raw mutex is used to guard access to the internal state. It is held for very short
+/// periods only, while locking and unlocking. It is *not* held for the entire time the
+/// async RwLock is locked.
+///
+/// Which implementation you select depends on the context in which you're using the lock.
+///
+/// Use [`CriticalSectionRawMutex`](crate::blocking_mutex::raw::CriticalSectionRawMutex) when data can be shared between threads and interrupts.
+///
+/// Use [`NoopRawMutex`](crate::blocking_mutex::raw::NoopRawMutex) when data is only shared between tasks running on the same executor.
+///
+/// Use [`ThreadModeRawMutex`](crate::blocking_mutex::raw::ThreadModeRawMutex) when data is shared between tasks running on the same executor but you want a singleton.
+///
+pub struct RwLock<M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    state: BlockingMutex<M, RefCell<State>>,
+    inner: UnsafeCell<T>,
+}
+
+unsafe impl<M: RawMutex + Send, T: ?Sized + Send> Send for RwLock<M, T> {}
+unsafe impl<M: RawMutex + Sync, T: ?Sized + Send + Sync> Sync for RwLock<M, T> {}
+
+/// Async read-write lock.
+impl<M, T> RwLock<M, T>
+where
+    M: RawMutex,
+{
+    /// Create a new read-write lock with the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: UnsafeCell::new(value),
+            state: BlockingMutex::new(RefCell::new(State {
+                readers: 0,
+                writer: false,
+                writers_waiting: 0,
+                wakers: WakerList::new(),
+            })),
+        }
+    }
+}
+
+impl<M, T> RwLock<M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Lock the read-write lock for reading.
+    ///
+    /// This will wait while the lock is held by a writer, or while a writer is waiting for it.
+    pub async fn read(&self) -> RwLockReadGuard<'_, M, T> {
+        let mut waiter = Waiter::new(self);
+
+        poll_fn(|cx| {
+            let ready = self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.can_read() {
+                    s.readers += 1;
+                    true
+                } else {
+                    // Safety: `waiter` stays in place in this future until it is dropped.
+                    unsafe { s.wakers.register(&waiter.entry, cx.waker()) };
+                    waiter.registered = true;
+                    false
+                }
+            });
+
+            if ready {
+                Poll::Ready(RwLockReadGuard { rwlock: self })
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Lock the read-write lock for writing.
+    ///
+    /// This will wait until there are no readers and no other writer.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, M, T> {
+        let mut waiter = Waiter::new(self);
+
+        poll_fn(|cx| {
+            let ready = self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.can_write() {
+                    if waiter.writer {
+                        waiter.writer = false;
+                        s.writers_waiting -= 1;
+                    }
+                    s.writer = true;
+                    true
+                } else {
+                    if !waiter.writer {
+                        waiter.writer = true;
+                        s.writers_waiting += 1;
+                    }
+                    // Safety: `waiter` stays in place in this future until it is dropped.
+                    unsafe { s.wakers.register(&waiter.entry, cx.waker()) };
+                    waiter.registered = true;
+                    false
+                }
+            });
+
+            if ready {
+                Poll::Ready(RwLockWriteGuard { rwlock: self })
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Attempt to immediately lock the read-write lock for reading.
+    ///
+    /// If the lock is held by a writer, or a writer is waiting for it, this will return an
+    /// error instead of waiting.
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, M, T>, TryLockError> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.can_read() {
+                s.readers += 1;
+                Ok(())
+            } else {
+                Err(TryLockError)
+            }
+        })?;
+
+        Ok(RwLockReadGuard { rwlock: self })
+    }
+
+    /// Attempt to immediately lock the read-write lock for writing.
+    ///
+    /// If the lock is already held by a reader or writer, this will return an error instead of waiting.
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, M, T>, TryLockError> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.can_write() {
+                s.writer = true;
+                Ok(())
+            } else {
+                Err(TryLockError)
+            }
+        })?;
+
+        Ok(RwLockWriteGuard { rwlock: self })
+    }
+
+    /// Consumes this read-write lock, returning the underlying data.
+    pub fn into_inner(self) -> T
+    where
+        T: Sized,
+    {
+        self.inner.into_inner()
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the RwLock mutably, no actual locking needs to
+    /// take place -- the mutable borrow statically guarantees no locks exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+/// A task waiting for the lock, in a `read` or `write` future.
+///
+/// When the future is dropped, the waiter leaves the list of waiting tasks. A pending writer is
+/// also withdrawn from the lock's bookkeeping if it didn't acquire the lock, so readers are not
+/// held back forever.
+struct Waiter<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    rwlock: &'a RwLock<M, T>,
+    entry: WakerListEntry,
+    /// Whether the entry was ever registered. If not, dropping doesn't need the lock.
+    registered: bool,
+    /// Whether this is a writer counted in `writers_waiting`.
+    writer: bool,
+}
+
+impl<'a, M, T> Waiter<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn new(rwlock: &'a RwLock<M, T>) -> Self {
+        Self {
+            rwlock,
+            entry: WakerListEntry::new(),
+            registered: false,
+            writer: false,
+        }
+    }
+}
+
+impl<'a, M, T> Drop for Waiter<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        if !self.registered {
+            return;
+        }
+
+        self.rwlock.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            s.wakers.remove(&self.entry);
+            if self.writer {
+                s.writers_waiting -= 1;
+                s.wakers.wake();
+            }
+        })
+    }
+}
+
+fn unlock_read<M: RawMutex>(state: &BlockingMutex<M, RefCell<State>>) {
+    state.lock(|s| {
+        let mut s = s.borrow_mut();
+        s.readers -= 1;
+        if s.readers == 0 {
+            s.wakers.wake();
+        }
+    })
+}
+
+fn unlock_write<M: RawMutex>(state: &BlockingMutex<M, RefCell<State>>) {
+    state.lock(|s| {
+        let mut s = s.borrow_mut();
+        s.writer = false;
+        s.wakers.wake();
+    })
+}
+
+/// Async read-write lock read guard.
+///
+/// Owning an instance of this type indicates having
+/// successfully locked the read-write lock for reading, and grants shared access to the contents.
+///
+/// Dropping it releases the read lock.
+pub struct RwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    rwlock: &'a RwLock<M, T>,
+}
+
+impl<'a, M, T> RwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&T) -> &U) -> MappedRwLockReadGuard<'a, M, U> {
+        let rwlock = this.rwlock;
+        let value = fun(unsafe { &*rwlock.inner.get() });
+        // Don't run the `drop` method for RwLockReadGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockReadGuard.
+        core::mem::forget(this);
+        MappedRwLockReadGuard {
+            state: &rwlock.state,
+            value,
+        }
+    }
+}
+
+impl<'a, M, T> Drop for RwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        unlock_read(&self.rwlock.state)
+    }
+}
+
+impl<'a, M, T> Deref for RwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the RwLockReadGuard represents shared access to the contents
+        // of the lock, so it's OK to get it.
+        unsafe { &*(self.rwlock.inner.get() as *const T) }
+    }
+}
+
+/// Async read-write lock write guard.
+///
+/// Owning an instance of this type indicates having
+/// successfully locked the read-write lock for writing, and grants exclusive access to the contents.
+///
+/// Dropping it releases the write lock.
+pub struct RwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    rwlock: &'a RwLock<M, T>,
+}
+
+impl<'a, M, T> RwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&mut T) -> &mut U) -> MappedRwLockWriteGuard<'a, M, U> {
+        let rwlock = this.rwlock;
+        let value = fun(unsafe { &mut *rwlock.inner.get() });
+        // Don't run the `drop` method for RwLockWriteGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockWriteGuard.
+        core::mem::forget(this);
+        MappedRwLockWriteGuard {
+            state: &rwlock.state,
+            value,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, M, T> Drop for RwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        unlock_write(&self.rwlock.state)
+    }
+}
+
+impl<'a, M, T> Deref for RwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the RwLockWriteGuard represents exclusive access to the contents
+        // of the lock, so it's OK to get it.
+        unsafe { &*(self.rwlock.inner.get() as *const T) }
+    }
+}
+
+impl<'a, M, T> DerefMut for RwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the RwLockWriteGuard represents exclusive access to the contents
+        // of the lock, so it's OK to get it.
+        unsafe { &mut *(self.rwlock.inner.get()) }
+    }
+}
+
+/// A handle to a held read lock that has had a function applied to it via [`RwLockReadGuard::map`] or
+/// [`MappedRwLockReadGuard::map`].
+///
+/// This can be used to hold a subfield of the protected data.
+pub struct MappedRwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    state: &'a BlockingMutex<M, RefCell<State>>,
+    value: &'a T,
+}
+
+impl<'a, M, T> MappedRwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&T) -> &U) -> MappedRwLockReadGuard<'a, M, U> {
+        let state = this.state;
+        let value = fun(this.value);
+        // Don't run the `drop` method for MappedRwLockReadGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockReadGuard.
+        core::mem::forget(this);
+        MappedRwLockReadGuard { state, value }
+    }
+}
+
+impl<'a, M, T> Drop for MappedRwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        unlock_read(self.state)
+    }
+}
+
+impl<'a, M, T> Deref for MappedRwLockReadGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+/// A handle to a held write lock that has had a function applied to it via [`RwLockWriteGuard::map`] or
+/// [`MappedRwLockWriteGuard::map`].
+///
+/// This can be used to hold a subfield of the protected data.
+pub struct MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    state: &'a BlockingMutex<M, RefCell<State>>,
+    value: *mut T,
+    _phantom: PhantomData<&'a mut T>,
+}
+
+impl<'a, M, T> MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&mut T) -> &mut U) -> MappedRwLockWriteGuard<'a, M, U> {
+        let state = this.state;
+        let value = fun(unsafe { &mut *this.value });
+        // Don't run the `drop` method for MappedRwLockWriteGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockWriteGuard.
+        core::mem::forget(this);
+        MappedRwLockWriteGuard {
+            state,
+            value,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+unsafe impl<'a, M, T> Send for MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex + Sync,
+    T: Send + ?Sized,
+{
+}
+
+unsafe impl<'a, M, T> Sync for MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex + Sync,
+    T: Sync + ?Sized,
+{
+}
+
+impl<'a, M, T> Drop for MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        unlock_write(self.state)
+    }
+}
+
+impl<'a, M, T> Deref for MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the MappedRwLockWriteGuard represents exclusive access to the contents
+        // of the lock, so it's OK to get it.
+        unsafe { &*(self.value as *const T) }
+    }
+}
+
+impl<'a, M, T> DerefMut for MappedRwLockWriteGuard<'a, M, T>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the MappedRwLockWriteGuard represents exclusive access to the contents
+        // of the lock, so it's OK to get it.
+        unsafe { &mut *(self.value) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll};
+
+    use futures_util::task::noop_waker_ref;
+
+    use super::*;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    #[test]
+    fn multiple_readers() {
+        let rwlock = RwLock::<NoopRawMutex, u32>::new(5);
+
+        let r1 = rwlock.try_read().unwrap();
+        let r2 = rwlock.try_read().unwrap();
+        assert_eq!(*r1, 5);
+        assert_eq!(*r2, 5);
+        assert_eq!(rwlock.try_write().err(), Some(TryLockError));
+
+        drop(r1);
+        assert!(rwlock.try_write().is_err());
+        drop(r2);
+        assert!(rwlock.try_write().is_ok());
+    }
+
+    #[test]
+    fn writer_excludes_readers() {
+        let rwlock = RwLock::<NoopRawMutex, u32>::new(5);
+
+        let mut w = rwlock.try_write().unwrap();
+        *w = 6;
+        assert!(rwlock.try_read().is_err());
+        assert!(rwlock.try_write().is_err());
+        drop(w);
+
+        assert_eq!(*rwlock.try_read().unwrap(), 6);
+    }
+
+    #[test]
+    fn waiting_writer_blocks_new_readers() {
+        let rwlock = RwLock::<NoopRawMutex, u32>::new(5);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let r = rwlock.try_read().unwrap();
+
+        {
+            let mut write = pin!(rwlock.write());
+            assert!(write.as_mut().poll(&mut cx).is_pending());
+
+            // A writer is queued, so new readers must wait.
+            assert!(rwlock.try_read().is_err());
+
+            drop(r);
+            let Poll::Ready(w) = write.as_mut().poll(&mut cx) else {
+                panic!("writer should acquire the lock once the reader is gone");
+            };
+            drop(w);
+        }
+
+        assert!(rwlock.try_read().is_ok());
+    }
+
+    #[test]
+    fn cancelled_writer_releases_readers() {
+        let rwlock = RwLock::<NoopRawMutex, u32>::new(5);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let _r = rwlock.try_read().unwrap();
+
+        {
+            let mut write = pin!(rwlock.write());
+            assert!(write.as_mut().poll(&mut cx).is_pending());
+            assert!(rwlock.try_read().is_err());
+        }
+
+        assert!(rwlock.try_read().is_ok());
+    }
+
+    #[test]
+    fn writer_wakes_all_waiting_readers() {
+        let rwlock = RwLock::<NoopRawMutex, u32>::new(5);
+        let (waker1, count1) = futures_test::task::new_count_waker();
+        let (waker2, count2) = futures_test::task::new_count_waker();
+
+        let w = rwlock.try_write().unwrap();
+
+        let mut read1 = pin!(rwlock.read());
+        let mut read2 = pin!(rwlock.read());
+        assert!(read1.as_mut().poll(&mut Context::from_waker(&waker1)).is_pending());
+        assert!(read2.as_mut().poll(&mut Context::from_waker(&waker2)).is_pending());
+        assert_eq!(count1, 0);
+        assert_eq!(count2, 0);
+
+        // A reader that stops waiting leaves the waiter list
+        let (waker3, count3) = futures_test::task::new_count_waker();
+        {
+            let mut read3 = pin!(rwlock.read());
+            assert!(read3.as_mut().poll(&mut Context::from_waker(&waker3)).is_pending());
+        }
+
+        drop(w);
+        assert_eq!(count1, 1);
+        assert_eq!(count2, 1);
+        assert_eq!(count3, 0);
+
+        assert!(read1.as_mut().poll(&mut Context::from_waker(&waker1)).is_ready());
+        assert!(read2.as_mut().poll(&mut Context::from_waker(&waker2)).is_ready());
+    }
+
+    fn assert_send<F: Future + Send>(_: F) {}
+
+    #[test]
+    fn futures_are_send() {
+        let rwlock = RwLock::<CriticalSectionRawMutex, u32>::new(5);
+        assert_send(rwlock.read());
+        assert_send(rwlock.write());
+    }
+
+    #[futures_test::test]
+    async fn mapped_guards() {
+        let rwlock = RwLock::<NoopRawMutex, (u32, u32)>::new((1, 2));
+
+        {
+            let w = rwlock.write().await;
+            let mut w = RwLockWriteGuard::map(w, |v| &mut v.1);
+            *w = 3;
+            assert!(rwlock.try_read().is_err());
+        }
+
+        let r = RwLockReadGuard::map(rwlock.read().await, |v| &v.1);
+        assert_eq!(*r, 3);
+        assert!(rwlock.try_write().is_err());
+        drop(r);
+
+        assert!(rwlock.try_write().is_ok());
+    }
+}
@@ -0,0 +1,473 @@
+//! Async counting semaphores.
+//!
+//! This module provides semaphores that limit how many tasks can use a shared resource at the same time,
+//! e.g. concurrent users of a DMA channel or the number of open connection slots.
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use heapless::Deque;
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// Error returned by [`FairSemaphore::acquire`] when all waiter slots are in use.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WaitQueueFull;
+
+/// A semaphore whose permits can be returned through a [`SemaphoreReleaser`].
+pub trait Release {
+    /// Return `permits` to the semaphore, waking tasks waiting to acquire them.
+    fn release(&self, permits: usize);
+}
+
+/// A greedy counting semaphore.
+///
+/// Any task may take permits as soon as enough of them are available, regardless of how long
+/// other tasks have been waiting. This means a task requesting a large number of permits can
+/// be starved by tasks requesting few. Use [`FairSemaphore`] if that is a concern.
+///
+/// The semaphore is generic over a blocking [`RawMutex`](crate::blocking_mutex::raw::RawMutex),
+/// which is only held while the permit count is updated.
+///
+/// Up to `N` tasks can wait for permits at the same time. If more tasks wait, all the waiting
+/// tasks are woken early to make room, and they register again when polled.
+pub struct Semaphore<M, const N: usize>
+where
+    M: RawMutex,
+{
+    state: Mutex<M, RefCell<SemaphoreState<N>>>,
+}
+
+struct SemaphoreState<const N: usize> {
+    permits: usize,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<M, const N: usize> Semaphore<M, N>
+where
+    M: RawMutex,
+{
+    /// Create a new semaphore with the given number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(SemaphoreState {
+                permits,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Acquire `permits` from the semaphore.
+    ///
+    /// This will wait until enough permits are available. The permits are returned to the
+    /// semaphore when the returned [`SemaphoreReleaser`] is dropped.
+    pub async fn acquire(&self, permits: usize) -> SemaphoreReleaser<'_, Self> {
+        poll_fn(|cx| {
+            self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.permits >= permits {
+                    s.permits -= permits;
+                    Poll::Ready(SemaphoreReleaser {
+                        semaphore: self,
+                        permits,
+                    })
+                } else {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    /// Attempt to immediately acquire `permits` from the semaphore.
+    ///
+    /// Returns `None` if not enough permits are available.
+    pub fn try_acquire(&self, permits: usize) -> Option<SemaphoreReleaser<'_, Self>> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.permits >= permits {
+                s.permits -= permits;
+                Some(SemaphoreReleaser {
+                    semaphore: self,
+                    permits,
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Add `permits` to the semaphore.
+    ///
+    /// This can be used to raise the limit at runtime, or to return permits that were
+    /// previously kept with [`SemaphoreReleaser::disarm`].
+    pub fn add_permits(&self, permits: usize) {
+        self.release(permits)
+    }
+
+    /// The number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.state.lock(|s| s.borrow().permits)
+    }
+}
+
+impl<M, const N: usize> Release for Semaphore<M, N>
+where
+    M: RawMutex,
+{
+    fn release(&self, permits: usize) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            s.permits += permits;
+            s.wakers.wake();
+        })
+    }
+}
+
+/// A fair counting semaphore.
+///
+/// Tasks acquire permits in the order in which they started waiting: as long as a task is waiting,
+/// later requests are queued behind it even if there would be enough permits for them. This
+/// prevents requests for many permits from being starved by requests for few.
+///
+/// At most `N` tasks can be waiting at the same time. Further calls to [`acquire`](Self::acquire)
+/// fail with [`WaitQueueFull`].
+///
+/// The semaphore is generic over a blocking [`RawMutex`](crate::blocking_mutex::raw::RawMutex),
+/// which is only held while the permit count and wait queue are updated.
+pub struct FairSemaphore<M, const N: usize>
+where
+    M: RawMutex,
+{
+    state: Mutex<M, RefCell<FairSemaphoreState<N>>>,
+}
+
+struct FairSemaphoreState<const N: usize> {
+    permits: usize,
+    /// Tickets of the waiting tasks, in arrival order.
+    queue: Deque<usize, N>,
+    next_ticket: usize,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<const N: usize> FairSemaphoreState<N> {
+    fn remove(&mut self, ticket: usize) {
+        let was_front = self.queue.front() == Some(&ticket);
+        for _ in 0..self.queue.len() {
+            let t = self.queue.pop_front().unwrap();
+            if t != ticket {
+                // We just popped an element, so there is room for it.
+                self.queue.push_back(t).ok().unwrap();
+            }
+        }
+        if was_front {
+            self.wakers.wake();
+        }
+    }
+}
+
+impl<M, const N: usize> FairSemaphore<M, N>
+where
+    M: RawMutex,
+{
+    /// Create a new fair semaphore with the given number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(FairSemaphoreState {
+                permits,
+                queue: Deque::new(),
+                next_ticket: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Acquire `permits` from the semaphore.
+    ///
+    /// This will wait until all tasks that started waiting earlier have been served and enough
+    /// permits are available. The permits are returned to the semaphore when the returned
+    /// [`SemaphoreReleaser`] is dropped.
+    ///
+    /// Returns [`WaitQueueFull`] if the task would have to wait but `N` tasks are already waiting.
+    pub async fn acquire(&self, permits: usize) -> Result<SemaphoreReleaser<'_, Self>, WaitQueueFull> {
+        let mut waiting = Waiting {
+            semaphore: self,
+            ticket: None,
+        };
+
+        poll_fn(|cx| {
+            self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                match waiting.ticket {
+                    None if s.queue.is_empty() && s.permits >= permits => {
+                        s.permits -= permits;
+                        Poll::Ready(Ok(SemaphoreReleaser {
+                            semaphore: self,
+                            permits,
+                        }))
+                    }
+                    None => {
+                        let ticket = s.next_ticket;
+                        if s.queue.push_back(ticket).is_err() {
+                            return Poll::Ready(Err(WaitQueueFull));
+                        }
+                        s.next_ticket = s.next_ticket.wrapping_add(1);
+                        waiting.ticket = Some(ticket);
+                        s.wakers.register_or_wake(cx.waker());
+                        Poll::Pending
+                    }
+                    Some(ticket) if s.queue.front() == Some(&ticket) && s.permits >= permits => {
+                        s.queue.pop_front();
+                        s.permits -= permits;
+                        waiting.ticket = None;
+                        // The next waiter in line may be satisfied by the remaining permits.
+                        s.wakers.wake();
+                        Poll::Ready(Ok(SemaphoreReleaser {
+                            semaphore: self,
+                            permits,
+                        }))
+                    }
+                    Some(_) => {
+                        s.wakers.register_or_wake(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+
+    /// Attempt to immediately acquire `permits` from the semaphore.
+    ///
+    /// Returns `None` if not enough permits are available or other tasks are waiting.
+    pub fn try_acquire(&self, permits: usize) -> Option<SemaphoreReleaser<'_, Self>> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.queue.is_empty() && s.permits >= permits {
+                s.permits -= permits;
+                Some(SemaphoreReleaser {
+                    semaphore: self,
+                    permits,
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Add `permits` to the semaphore.
+    ///
+    /// This can be used to raise the limit at runtime, or to return permits that were
+    /// previously kept with [`SemaphoreReleaser::disarm`].
+    pub fn add_permits(&self, permits: usize) {
+        self.release(permits)
+    }
+
+    /// The number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.state.lock(|s| s.borrow().permits)
+    }
+}
+
+impl<M, const N: usize> Release for FairSemaphore<M, N>
+where
+    M: RawMutex,
+{
+    fn release(&self, permits: usize) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            s.permits += permits;
+            s.wakers.wake();
+        })
+    }
+}
+
+/// Removes a waiter from the queue if its `acquire` future is dropped before it got its permits,
+/// so it does not block the tasks behind it.
+struct Waiting<'a, M, const N: usize>
+where
+    M: RawMutex,
+{
+    semaphore: &'a FairSemaphore<M, N>,
+    ticket: Option<usize>,
+}
+
+impl<'a, M, const N: usize> Drop for Waiting<'a, M, N>
+where
+    M: RawMutex,
+{
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.semaphore.state.lock(|s| s.borrow_mut().remove(ticket))
+        }
+    }
+}
+
+/// A set of permits acquired from a semaphore.
+///
+/// Dropping it returns the permits to the semaphore.
+#[must_use = "permits are released immediately when the releaser is dropped"]
+pub struct SemaphoreReleaser<'a, S>
+where
+    S: Release + ?Sized,
+{
+    semaphore: &'a S,
+    permits: usize,
+}
+
+impl<'a, S> SemaphoreReleaser<'a, S>
+where
+    S: Release + ?Sized,
+{
+    /// The number of permits held by this releaser.
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Keep the permits instead of returning them to the semaphore on drop.
+    ///
+    /// Returns the number of permits that were held. They can be returned later with `add_permits`.
+    pub fn disarm(self) -> usize {
+        let permits = self.permits;
+        core::mem::forget(self);
+        permits
+    }
+}
+
+impl<'a, S> Drop for SemaphoreReleaser<'a, S>
+where
+    S: Release + ?Sized,
+{
+    fn drop(&mut self) {
+        self.semaphore.release(self.permits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::Context;
+
+    use futures_util::task::noop_waker_ref;
+
+    use super::*;
+    use crate::blocking_mutex::raw::NoopRawMutex;
+
+    #[test]
+    fn try_acquire_and_release() {
+        let semaphore = Semaphore::<NoopRawMutex, 1>::new(3);
+
+        let a = semaphore.try_acquire(2).unwrap();
+        assert_eq!(a.permits(), 2);
+        assert_eq!(semaphore.available_permits(), 1);
+        assert!(semaphore.try_acquire(2).is_none());
+
+        drop(a);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn disarm_and_add_permits() {
+        let semaphore = Semaphore::<NoopRawMutex, 1>::new(1);
+
+        assert_eq!(semaphore.try_acquire(1).unwrap().disarm(), 1);
+        assert_eq!(semaphore.available_permits(), 0);
+
+        semaphore.add_permits(2);
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[futures_test::test]
+    async fn acquire_waits_for_release() {
+        let semaphore = Semaphore::<NoopRawMutex, 1>::new(1);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let a = semaphore.acquire(1).await;
+        let mut b = pin!(semaphore.acquire(1));
+        assert!(b.as_mut().poll(&mut cx).is_pending());
+
+        drop(a);
+        assert!(b.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn release_wakes_all_waiters() {
+        let semaphore = Semaphore::<NoopRawMutex, 2>::new(0);
+        let (waker1, count1) = futures_test::task::new_count_waker();
+        let (waker2, count2) = futures_test::task::new_count_waker();
+
+        let mut a = pin!(semaphore.acquire(1));
+        let mut b = pin!(semaphore.acquire(1));
+        assert!(a.as_mut().poll(&mut Context::from_waker(&waker1)).is_pending());
+        assert!(b.as_mut().poll(&mut Context::from_waker(&waker2)).is_pending());
+
+        semaphore.add_permits(2);
+        assert_eq!(count1, 1);
+        assert_eq!(count2, 1);
+
+        assert!(a.as_mut().poll(&mut Context::from_waker(&waker1)).is_ready());
+        assert!(b.as_mut().poll(&mut Context::from_waker(&waker2)).is_ready());
+    }
+
+    #[test]
+    fn fair_semaphore_serves_in_order() {
+        let semaphore = FairSemaphore::<NoopRawMutex, 2>::new(2);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let a = semaphore.try_acquire(1).unwrap();
+
+        // A large request queues up...
+        let mut large = pin!(semaphore.acquire(2));
+        assert!(large.as_mut().poll(&mut cx).is_pending());
+
+        // ... and small requests can't overtake it, even though a permit is available.
+        assert!(semaphore.try_acquire(1).is_none());
+        let mut small = pin!(semaphore.acquire(1));
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        drop(a);
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+        let large = match large.as_mut().poll(&mut cx) {
+            Poll::Ready(Ok(r)) => r,
+            _ => panic!("large request should be served first"),
+        };
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        drop(large);
+        assert!(matches!(small.as_mut().poll(&mut cx), Poll::Ready(Ok(_))));
+    }
+
+    #[test]
+    fn fair_semaphore_wait_queue_full() {
+        let semaphore = FairSemaphore::<NoopRawMutex, 1>::new(0);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let mut a = pin!(semaphore.acquire(1));
+        assert!(a.as_mut().poll(&mut cx).is_pending());
+
+        let mut b = pin!(semaphore.acquire(1));
+        assert_eq!(
+            b.as_mut().poll(&mut cx).map(|r| r.err()),
+            Poll::Ready(Some(WaitQueueFull))
+        );
+    }
+
+    #[test]
+    fn fair_semaphore_cancelled_waiter_leaves_queue() {
+        let semaphore = FairSemaphore::<NoopRawMutex, 2>::new(1);
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        {
+            let mut large = pin!(semaphore.acquire(2));
+            assert!(large.as_mut().poll(&mut cx).is_pending());
+            assert!(semaphore.try_acquire(1).is_none());
+        }
+
+        assert!(semaphore.try_acquire(1).is_some());
+    }
+}
@@ -0,0 +1,378 @@
+//! A lock-free queue for sending values from a single producer to a single consumer.
+//!
+//! Unlike the other channels in this crate, [`Channel`] is not protected by a [`RawMutex`](crate::blocking_mutex::raw::RawMutex).
+//! Sending and receiving only uses atomic loads and stores, so it works without critical sections
+//! on every target, including the ones without compare-and-swap like `thumbv6m`. This makes it a
+//! good fit for passing data from an interrupt handler to a task, or the other way around.
+//!
+//! A critical section is only taken to register and wake a waker, when a task actually waits for
+//! the other side.
+//!
+//! The channel is split into a [`Producer`] and a [`Consumer`], which can be moved into different
+//! tasks or interrupt handlers.
+
+use core::cell::UnsafeCell;
+use core::future::poll_fn;
+use core::mem::MaybeUninit;
+use core::sync::atomic::fence;
+use core::task::{Context, Poll};
+
+use atomic_polyfill::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::waitqueue::AtomicWaker;
+
+/// A lock-free single producer single consumer channel holding up to `N` values.
+///
+/// ## Example
+///
+/// ```
+/// # use embassy_sync::spsc::Channel;
+/// # use futures_executor::block_on;
+/// # let test = async {
+/// let mut channel = Channel::<u32, 4>::new();
+/// let (mut producer, mut consumer) = channel.split();
+///
+/// producer.send(1).await;
+/// assert_eq!(producer.try_send(2), Ok(()));
+///
+/// assert_eq!(consumer.recv().await, 1);
+/// assert_eq!(consumer.try_recv(), Some(2));
+/// assert_eq!(consumer.try_recv(), None);
+/// # };
+/// #
+/// # block_on(test);
+/// ```
+pub struct Channel<T, const N: usize> {
+    buf: UnsafeCell<MaybeUninit<[T; N]>>,
+    // start and end wrap at N*2, not at N.
+    // This allows distinguishing "full" and "empty":
+    // full is when start+N == end (modulo N*2)
+    // empty is when start == end
+    /// Index of the next value to receive, only written by the consumer.
+    start: AtomicUsize,
+    /// Index of the next slot to send to, only written by the producer.
+    end: AtomicUsize,
+    recv_waker: Waiter,
+    send_waker: Waiter,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for Channel<T, N> {}
+
+impl<T, const N: usize> Channel<T, N> {
+    /// Create a new empty channel.
+    pub const fn new() -> Self {
+        ::core::assert!(N > 0, "the capacity of the channel must be greater than zero");
+        Self {
+            buf: UnsafeCell::new(MaybeUninit::uninit()),
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+            recv_waker: Waiter::new(),
+            send_waker: Waiter::new(),
+        }
+    }
+
+    /// Split the channel into its producer and consumer halves.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { channel: self }, Consumer { channel: self })
+    }
+
+    /// Get the number of values in the channel.
+    pub fn len(&self) -> usize {
+        let start = self.start.load(Ordering::Acquire);
+        let end = self.end.load(Ordering::Acquire);
+        self.distance(start, end)
+    }
+
+    /// Check if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check if the channel is full.
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// The maximum number of values the channel can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    fn slot(&self, index: usize) -> *mut T {
+        // Safety: `index % N` is in bounds of the buffer.
+        unsafe { (self.buf.get() as *mut T).add(index % N) }
+    }
+
+    fn distance(&self, start: usize, end: usize) -> usize {
+        if end >= start {
+            end - start
+        } else {
+            2 * N + end - start
+        }
+    }
+
+    fn wrap(&self, n: usize) -> usize {
+        if n == 2 * N {
+            0
+        } else {
+            n
+        }
+    }
+
+    /// Push a value. Must only be called by the producer.
+    fn try_send(&self, value: T) -> Result<(), T> {
+        // Ordering: `end` is only written by us, and the slot is only read by the consumer after
+        // loading `end` with `Acquire`.
+        let end = self.end.load(Ordering::Relaxed);
+        let start = self.start.load(Ordering::Acquire);
+        if self.distance(start, end) == N {
+            return Err(value);
+        }
+
+        // Safety: the slot at `end` is not filled, so the consumer does not access it.
+        unsafe { self.slot(end).write(value) };
+        self.end.store(self.wrap(end + 1), Ordering::Release);
+        self.recv_waker.wake();
+        Ok(())
+    }
+
+    /// Pop a value. Must only be called by the consumer.
+    fn try_recv(&self) -> Option<T> {
+        // Ordering: `start` is only written by us, and the slot is only written by the producer
+        // after loading `start` with `Acquire`.
+        let start = self.start.load(Ordering::Relaxed);
+        let end = self.end.load(Ordering::Acquire);
+        if start == end {
+            return None;
+        }
+
+        // Safety: the slot at `start` is filled, so the producer does not access it.
+        let value = unsafe { self.slot(start).read() };
+        self.start.store(self.wrap(start + 1), Ordering::Release);
+        self.send_waker.wake();
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Drop for Channel<T, N> {
+    fn drop(&mut self) {
+        while self.try_recv().is_some() {}
+    }
+}
+
+/// A waker that is only woken when a task is waiting, so the side that doesn't wait never takes
+/// a critical section.
+struct Waiter {
+    waiting: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Waiter {
+    const fn new() -> Self {
+        Self {
+            waiting: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Poll `f`, registering the waker of the context if it's not ready.
+    fn poll<R>(&self, cx: &mut Context<'_>, mut f: impl FnMut() -> Option<R>) -> Poll<R> {
+        if let Some(r) = f() {
+            return Poll::Ready(r);
+        }
+
+        self.waker.register(cx.waker());
+        self.waiting.store(true, Ordering::Relaxed);
+        // Ordering: either the other side sees `waiting` after updating its index, or we see
+        // the updated index when trying again.
+        fence(Ordering::SeqCst);
+
+        match f() {
+            Some(r) => Poll::Ready(r),
+            None => Poll::Pending,
+        }
+    }
+
+    fn wake(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) {
+            self.waiting.store(false, Ordering::Relaxed);
+            self.waker.wake();
+        }
+    }
+}
+
+/// The sending half of a [`Channel`].
+pub struct Producer<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<'a, T, const N: usize> Producer<'a, T, N> {
+    /// Attempt to send a value, returning it back if the channel is full.
+    ///
+    /// This never takes a critical section, unless the consumer is waiting for a value.
+    pub fn try_send(&mut self, value: T) -> Result<(), T> {
+        self.channel.try_send(value)
+    }
+
+    /// Send a value, waiting until there is space in the channel.
+    pub async fn send(&mut self, value: T) {
+        let mut value = Some(value);
+        poll_fn(|cx| {
+            self.channel
+                .send_waker
+                .poll(cx, || match self.channel.try_send(unwrap!(value.take())) {
+                    Ok(()) => Some(()),
+                    Err(v) => {
+                        value = Some(v);
+                        None
+                    }
+                })
+        })
+        .await
+    }
+
+    /// Get the number of values in the channel.
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Check if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Check if the channel is full.
+    pub fn is_full(&self) -> bool {
+        self.channel.is_full()
+    }
+
+    /// The number of values that can be sent without waiting.
+    pub fn free_capacity(&self) -> usize {
+        N - self.channel.len()
+    }
+}
+
+/// The receiving half of a [`Channel`].
+pub struct Consumer<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    /// Attempt to receive a value, returning `None` if the channel is empty.
+    ///
+    /// This never takes a critical section, unless the producer is waiting for space.
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.channel.try_recv()
+    }
+
+    /// Poll the channel for a value, registering the waker of the context if it is empty.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        self.channel.recv_waker.poll(cx, || self.channel.try_recv())
+    }
+
+    /// Receive a value, waiting until there is one.
+    pub async fn recv(&mut self) -> T {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Get the number of values in the channel.
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    /// Check if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+
+    /// Check if the channel is full.
+    pub fn is_full(&self) -> bool {
+        self.channel.is_full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+    use static_cell::StaticCell;
+
+    use super::*;
+
+    #[test]
+    fn send_recv() {
+        let mut channel = Channel::<u32, 3>::new();
+        let (mut producer, mut consumer) = channel.split();
+
+        assert!(producer.is_empty());
+        assert_eq!(producer.try_send(1), Ok(()));
+        assert_eq!(producer.try_send(2), Ok(()));
+        assert_eq!(producer.try_send(3), Ok(()));
+        assert_eq!(producer.try_send(4), Err(4));
+        assert!(consumer.is_full());
+
+        // Wrap around a couple of times
+        for i in 4..20 {
+            assert_eq!(consumer.try_recv(), Some(i - 3));
+            assert_eq!(producer.try_send(i), Ok(()));
+            assert_eq!(consumer.len(), 3);
+        }
+        assert_eq!(consumer.try_recv(), Some(17));
+        assert_eq!(consumer.try_recv(), Some(18));
+        assert_eq!(consumer.try_recv(), Some(19));
+        assert_eq!(consumer.try_recv(), None);
+        assert_eq!(producer.free_capacity(), 3);
+    }
+
+    #[test]
+    fn drops_remaining_values() {
+        struct Counted<'a>(&'a Cell<u32>);
+
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut channel = Channel::<_, 3>::new();
+        let (mut producer, mut consumer) = channel.split();
+        assert!(producer.try_send(Counted(&drops)).is_ok());
+        assert!(producer.try_send(Counted(&drops)).is_ok());
+        drop(consumer.try_recv());
+        assert_eq!(drops.get(), 1);
+        drop(channel);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[futures_test::test]
+    async fn producer_and_consumer_wait() {
+        let executor = ThreadPool::new().unwrap();
+
+        static CHANNEL: StaticCell<Channel<u32, 2>> = StaticCell::new();
+        let (mut producer, mut consumer) = CHANNEL.init(Channel::new()).split();
+
+        executor
+            .spawn(async move {
+                for i in 0..100 {
+                    producer.send(i).await;
+                    if i % 10 == 0 {
+                        Delay::new(Duration::from_millis(5)).await;
+                    }
+                }
+            })
+            .unwrap();
+
+        for i in 0..100 {
+            if i % 25 == 0 {
+                Delay::new(Duration::from_millis(10)).await;
+            }
+            assert_eq!(consumer.recv().await, i);
+        }
+    }
+}
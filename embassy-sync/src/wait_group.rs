@@ -0,0 +1,146 @@
+//! A synchronization primitive for waiting until a group of tasks has finished.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A counter of pending work that tasks can wait on to reach zero.
+///
+/// Work is added with [`add`](WaitGroup::add) before starting it, and marked as finished with
+/// [`done`](WaitGroup::done). [`wait`](WaitGroup::wait) completes once all work is done.
+///
+/// `N` is the amount of wakers that can be registered at once: when more tasks wait, the waiting
+/// tasks are woken up more often than necessary, but no wakeup is lost.
+///
+/// ## Example
+///
+/// ```
+/// # use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// # use embassy_sync::wait_group::WaitGroup;
+/// # use futures_executor::block_on;
+/// # let test = async {
+/// let workers = WaitGroup::<NoopRawMutex, 1>::new();
+///
+/// workers.add(2);
+/// let first = async {
+///     // ... do some work ...
+///     workers.done();
+/// };
+/// let second = async {
+///     // ... do some other work ...
+///     workers.done();
+/// };
+///
+/// futures_util::join!(first, second, workers.wait());
+/// assert_eq!(workers.count(), 0);
+/// # };
+/// #
+/// # block_on(test);
+/// ```
+pub struct WaitGroup<M: RawMutex, const N: usize> {
+    inner: Mutex<M, RefCell<State<N>>>,
+}
+
+struct State<const N: usize> {
+    count: usize,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<M: RawMutex, const N: usize> WaitGroup<M, N> {
+    /// Create a new `WaitGroup` without pending work.
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(State {
+                count: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Add `n` pieces of pending work.
+    pub fn add(&self, n: usize) {
+        self.inner.lock(|s| s.borrow_mut().count += n)
+    }
+
+    /// Mark a piece of work as done, waking the waiting tasks if it was the last one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no pending work.
+    pub fn done(&self) {
+        self.inner.lock(|s| {
+            let mut s = s.borrow_mut();
+            assert!(s.count > 0, "WaitGroup::done called without pending work");
+            s.count -= 1;
+            if s.count == 0 {
+                s.wakers.wake();
+            }
+        })
+    }
+
+    /// The amount of pending work.
+    pub fn count(&self) -> usize {
+        self.inner.lock(|s| s.borrow().count)
+    }
+
+    /// Wait until all pending work is done. If there is no pending work, this returns immediately.
+    pub async fn wait(&self) {
+        poll_fn(|cx| {
+            self.inner.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.count == 0 {
+                    Poll::Ready(())
+                } else {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+
+    use super::*;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    #[futures_test::test]
+    async fn waits_for_workers() {
+        let executor = ThreadPool::new().unwrap();
+
+        static WORKERS: WaitGroup<CriticalSectionRawMutex, 2> = WaitGroup::new();
+
+        WORKERS.add(3);
+        for i in 0..3 {
+            executor
+                .spawn(async move {
+                    Delay::new(Duration::from_millis(50 * (i + 1))).await;
+                    WORKERS.done();
+                })
+                .unwrap();
+        }
+
+        let other = executor.spawn_with_handle(WORKERS.wait()).unwrap();
+        WORKERS.wait().await;
+        other.await;
+        assert_eq!(WORKERS.count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn done_without_work() {
+        let workers = WaitGroup::<NoopRawMutex, 1>::new();
+        workers.done();
+    }
+}
@@ -5,3 +5,6 @@ pub use waker::*;
 
 mod multi_waker;
 pub use multi_waker::*;
+
+mod waker_list;
+pub(crate) use waker_list::*;
@@ -24,6 +24,24 @@ impl<const N: usize> MultiWakerRegistration<N> {
         }
     }
 
+    /// Register a waker, making room for it if the buffer is full.
+    ///
+    /// Nothing happens if a waker for the same task is already registered. If the buffer is full,
+    /// all registered wakers are woken to make room: the tasks that are still waiting simply
+    /// register again when they're polled. If `N` is 0, `w` is woken right away instead.
+    pub fn register_or_wake(&mut self, w: &Waker) {
+        if self.wakers.iter().any(|waker_slot| waker_slot.will_wake(w)) {
+            return;
+        }
+
+        if self.register(w).is_err() {
+            self.wake();
+            if self.register(w).is_err() {
+                w.wake_by_ref();
+            }
+        }
+    }
+
     /// Wake all registered wakers. This clears the buffer
     pub fn wake(&mut self) {
         for waker_slot in self.wakers.iter_mut() {
@@ -31,3 +49,33 @@ impl<const N: usize> MultiWakerRegistration<N> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures_test::task::new_count_waker;
+
+    use super::*;
+
+    #[test]
+    fn register_or_wake() {
+        let (wa, a) = new_count_waker();
+        let (wb, b) = new_count_waker();
+
+        let mut wakers = MultiWakerRegistration::<1>::new();
+        wakers.register_or_wake(&wa);
+        // Registering the same task again doesn't need room
+        wakers.register_or_wake(&wa);
+        assert_eq!(a.get(), 0);
+
+        // The buffer is full, so `a` is woken to make room
+        wakers.register_or_wake(&wb);
+        assert_eq!(a.get(), 1);
+        wakers.wake();
+        assert_eq!(b.get(), 1);
+
+        // Without room at all, the waker is woken right away
+        let mut none = MultiWakerRegistration::<0>::new();
+        none.register_or_wake(&wa);
+        assert_eq!(a.get(), 2);
+    }
+}
@@ -53,6 +53,10 @@ impl WakerRegistration {
     pub fn occupied(&self) -> bool {
         self.waker.is_some()
     }
+    /// Returns true if the registered waker, if any, wakes the same task as `w`.
+    pub(crate) fn will_wake(&self, w: &Waker) -> bool {
+        matches!(self.waker, Some(ref w2) if w2.will_wake(w))
+    }
 }
 
 /// Utility struct to register and wake a waker.
@@ -0,0 +1,145 @@
+use core::cell::Cell;
+use core::marker::PhantomPinned;
+use core::ptr::NonNull;
+use core::task::Waker;
+
+/// Utility struct to register and wake any number of wakers.
+///
+/// The list doesn't store the wakers itself: every waiting task provides a [`WakerListEntry`],
+/// usually kept in the state of its future, which is linked into the list.
+pub(crate) struct WakerList {
+    head: Option<NonNull<WakerListEntry>>,
+}
+
+/// The place of a waiting task in a [`WakerList`].
+pub(crate) struct WakerListEntry {
+    waker: Cell<Option<Waker>>,
+    prev: Cell<Option<NonNull<WakerListEntry>>>,
+    next: Cell<Option<NonNull<WakerListEntry>>>,
+    linked: Cell<bool>,
+    _pinned: PhantomPinned,
+}
+
+// Entries are only accessed through the list they're linked into, which has to be borrowed
+// mutably for that.
+unsafe impl Send for WakerList {}
+unsafe impl Send for WakerListEntry {}
+unsafe impl Sync for WakerListEntry {}
+
+impl WakerList {
+    /// Create a new empty list
+    pub const fn new() -> Self {
+        Self { head: None }
+    }
+
+    /// Register a waker in `entry`, and link the entry into the list if it isn't yet.
+    ///
+    /// # Safety
+    ///
+    /// Once linked, `entry` must not be moved or dropped before it is [removed](Self::remove) from
+    /// the list, or unlinked by [`wake`](Self::wake). It must not be linked into another list.
+    pub unsafe fn register(&mut self, entry: &WakerListEntry, w: &Waker) {
+        match entry.waker.take() {
+            Some(waker) if waker.will_wake(w) => entry.waker.set(Some(waker)),
+            _ => entry.waker.set(Some(w.clone())),
+        }
+
+        if !entry.linked.replace(true) {
+            entry.prev.set(None);
+            entry.next.set(self.head);
+            if let Some(head) = self.head {
+                head.as_ref().prev.set(Some(entry.into()));
+            }
+            self.head = Some(entry.into());
+        }
+    }
+
+    /// Unlink `entry` from the list, if it is linked, dropping its waker.
+    ///
+    /// `entry` must not be linked into another list.
+    pub fn remove(&mut self, entry: &WakerListEntry) {
+        if !entry.linked.replace(false) {
+            return;
+        }
+
+        let (prev, next) = (entry.prev.take(), entry.next.take());
+        // Safety: linked entries are alive, see `register`.
+        unsafe {
+            match prev {
+                Some(prev) => prev.as_ref().next.set(next),
+                None => self.head = next,
+            }
+            if let Some(next) = next {
+                next.as_ref().prev.set(prev);
+            }
+        }
+        entry.waker.set(None);
+    }
+
+    /// Wake all registered wakers. This empties the list
+    pub fn wake(&mut self) {
+        let mut next = self.head.take();
+        while let Some(entry) = next {
+            // Safety: linked entries are alive, see `register`.
+            let entry = unsafe { entry.as_ref() };
+            next = entry.next.take();
+            entry.prev.set(None);
+            entry.linked.set(false);
+            if let Some(waker) = entry.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl WakerListEntry {
+    /// Create a new entry, not linked into any list
+    pub const fn new() -> Self {
+        Self {
+            waker: Cell::new(None),
+            prev: Cell::new(None),
+            next: Cell::new(None),
+            linked: Cell::new(false),
+            _pinned: PhantomPinned,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_test::task::new_count_waker;
+
+    use super::*;
+
+    #[test]
+    fn remove_and_wake() {
+        let (wa, a) = new_count_waker();
+        let (wb, b) = new_count_waker();
+        let (wc, c) = new_count_waker();
+        let (ea, eb, ec) = (WakerListEntry::new(), WakerListEntry::new(), WakerListEntry::new());
+
+        let mut list = WakerList::new();
+        unsafe {
+            list.register(&ea, &wa);
+            list.register(&eb, &wb);
+            list.register(&ec, &wc);
+            // Registering an entry again doesn't link it twice
+            list.register(&eb, &wb);
+        }
+
+        list.remove(&eb);
+        list.remove(&eb);
+        list.wake();
+        assert_eq!((a.get(), b.get(), c.get()), (1, 0, 1));
+
+        // The list is empty after waking
+        list.wake();
+        assert_eq!((a.get(), b.get(), c.get()), (1, 0, 1));
+
+        // Entries can be registered again
+        unsafe { list.register(&eb, &wb) };
+        list.remove(&ea);
+        list.wake();
+        assert_eq!((a.get(), b.get(), c.get()), (1, 1, 1));
+    }
+}
@@ -0,0 +1,519 @@
+//! A synchronization primitive for passing the latest value to **multiple** receivers.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::task::{Context, Poll};
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A state broadcast channel that always holds the latest value.
+///
+/// This is similar to a [`Signal`](crate::signal::Signal), except that up to `N` receivers can
+/// observe the value at the same time, and the value is kept after it has been read. Unlike a
+/// [`PubSubChannel`](crate::pubsub::PubSubChannel), no history is kept: a receiver that is slower
+/// than the sender only ever sees the most recent value.
+///
+/// This makes it a good fit for "state", like the current link state or the latest sensor reading.
+///
+/// Any number of senders can be created. Receivers take up one of the `N` receiver slots, which
+/// is freed when the receiver is dropped.
+///
+/// ## Example
+///
+/// ```
+/// # use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// # use embassy_sync::watch::Watch;
+/// # use futures_executor::block_on;
+/// # let test = async {
+/// // Create the watch. This can be static as well
+/// let watch = Watch::<NoopRawMutex, u32, 2>::new();
+///
+/// let sender = watch.sender();
+/// let mut rcv0 = watch.receiver().unwrap();
+/// // This is a dynamic receiver with a dynamic (trait object) reference to the watch
+/// let mut rcv1 = watch.dyn_receiver().unwrap();
+///
+/// sender.send(10);
+///
+/// // Both receivers see the new value
+/// assert_eq!(rcv0.changed().await, 10);
+/// assert_eq!(rcv1.changed().await, 10);
+///
+/// // Intermediate values may be skipped, only the latest one is observed
+/// sender.send(11);
+/// sender.send_modify(|v| *v.as_mut().unwrap() += 1);
+/// assert_eq!(rcv0.changed().await, 12);
+///
+/// // The current value can be read at any time
+/// assert_eq!(rcv1.try_get(), Some(12));
+/// assert_eq!(rcv1.try_changed(), None);
+/// # };
+/// #
+/// # block_on(test);
+/// ```
+pub struct Watch<M: RawMutex, T: Clone, const N: usize> {
+    mutex: Mutex<M, RefCell<WatchState<T, N>>>,
+}
+
+struct WatchState<T: Clone, const N: usize> {
+    data: Option<T>,
+    /// Incremented every time the value is changed. Receivers track the id of the last value they
+    /// have seen to know whether there is a new one.
+    current_id: u64,
+    wakers: MultiWakerRegistration<N>,
+    receiver_count: usize,
+}
+
+/// Error type for the [`Watch`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// All receiver slots are used. To add another receiver, first another receiver must be dropped or
+    /// the capacity of the watch must be increased.
+    MaximumReceiversReached,
+}
+
+mod sealed {
+    pub trait WatchReceivers {
+        /// Let the watch know that a receiver has dropped.
+        fn drop_receiver(&self);
+    }
+}
+
+/// 'Middle level' behaviour of the watch.
+/// This trait is used so that Snd and Rcv can be generic over the watch.
+///
+/// It is sealed, so that only receivers can give their slot back.
+pub trait WatchBehavior<T: Clone>: sealed::WatchReceivers {
+    /// Poll the watch for the current value, marking it as seen.
+    ///
+    /// If there is no value yet, the waker of the context is registered.
+    fn poll_get(&self, id: &mut u64, cx: &mut Context<'_>) -> Poll<T>;
+
+    /// Try to get the current value, marking it as seen if an id is given.
+    fn try_get(&self, id: Option<&mut u64>) -> Option<T>;
+
+    /// Poll the watch for a value that is newer than the one with the given id, marking it as seen.
+    ///
+    /// If there is no new value yet, the waker of the context is registered.
+    fn poll_changed(&self, id: &mut u64, cx: &mut Context<'_>) -> Poll<T>;
+
+    /// Try to get a value that is newer than the one with the given id, marking it as seen.
+    fn try_changed(&self, id: &mut u64) -> Option<T>;
+
+    /// Check whether the watch contains a value.
+    fn contains_value(&self) -> bool;
+
+    /// Replace the current value and wake all receivers.
+    fn send(&self, val: T);
+
+    /// Modify the current value in place and wake all receivers.
+    fn send_modify(&self, f: &mut dyn FnMut(&mut Option<T>));
+
+    /// Clear the current value. This does not wake the receivers.
+    fn clear(&self);
+}
+
+impl<M: RawMutex, T: Clone, const N: usize> sealed::WatchReceivers for Watch<M, T, N> {
+    fn drop_receiver(&self) {
+        self.mutex.lock(|state| state.borrow_mut().receiver_count -= 1)
+    }
+}
+
+impl<M: RawMutex, T: Clone, const N: usize> WatchBehavior<T> for Watch<M, T, N> {
+    fn poll_get(&self, id: &mut u64, cx: &mut Context<'_>) -> Poll<T> {
+        self.mutex.lock(|state| {
+            let mut s = state.borrow_mut();
+            match &s.data {
+                Some(data) => {
+                    let data = data.clone();
+                    *id = s.current_id;
+                    Poll::Ready(data)
+                }
+                None => {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    fn try_get(&self, id: Option<&mut u64>) -> Option<T> {
+        self.mutex.lock(|state| {
+            let s = state.borrow();
+            if let Some(id) = id {
+                *id = s.current_id;
+            }
+            s.data.clone()
+        })
+    }
+
+    fn poll_changed(&self, id: &mut u64, cx: &mut Context<'_>) -> Poll<T> {
+        self.mutex.lock(|state| {
+            let mut s = state.borrow_mut();
+            match (&s.data, s.current_id > *id) {
+                (Some(data), true) => {
+                    let data = data.clone();
+                    *id = s.current_id;
+                    Poll::Ready(data)
+                }
+                _ => {
+                    s.wakers.register_or_wake(cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    fn try_changed(&self, id: &mut u64) -> Option<T> {
+        self.mutex.lock(|state| {
+            let s = state.borrow();
+            match (&s.data, s.current_id > *id) {
+                (Some(data), true) => {
+                    *id = s.current_id;
+                    Some(data.clone())
+                }
+                _ => None,
+            }
+        })
+    }
+
+    fn contains_value(&self) -> bool {
+        self.mutex.lock(|state| state.borrow().data.is_some())
+    }
+
+    fn send(&self, val: T) {
+        self.mutex.lock(|state| {
+            let mut s = state.borrow_mut();
+            s.data = Some(val);
+            s.current_id += 1;
+            s.wakers.wake();
+        })
+    }
+
+    fn send_modify(&self, f: &mut dyn FnMut(&mut Option<T>)) {
+        self.mutex.lock(|state| {
+            let mut s = state.borrow_mut();
+            f(&mut s.data);
+            s.current_id += 1;
+            s.wakers.wake();
+        })
+    }
+
+    fn clear(&self) {
+        self.mutex.lock(|state| state.borrow_mut().data = None)
+    }
+}
+
+impl<M: RawMutex, T: Clone, const N: usize> Watch<M, T, N> {
+    /// Create a new `Watch` without a value.
+    pub const fn new() -> Self {
+        Self {
+            mutex: Mutex::new(RefCell::new(WatchState {
+                data: None,
+                current_id: 0,
+                wakers: MultiWakerRegistration::new(),
+                receiver_count: 0,
+            })),
+        }
+    }
+
+    /// Create a new sender.
+    pub fn sender(&self) -> Sender<'_, M, T, N> {
+        Sender(Snd::new(self))
+    }
+
+    /// Create a new sender with a dynamic (trait object) reference to the watch.
+    pub fn dyn_sender(&self) -> DynSender<'_, T> {
+        DynSender(Snd::new(self))
+    }
+
+    /// Create a new receiver. A value that is already present counts as changed for it.
+    ///
+    /// If there are no receiver slots left, an error will be returned.
+    pub fn receiver(&self) -> Result<Receiver<'_, M, T, N>, Error> {
+        self.register_receiver()?;
+        Ok(Receiver(Rcv::new(self)))
+    }
+
+    /// Create a new receiver with a dynamic (trait object) reference to the watch.
+    /// A value that is already present counts as changed for it.
+    ///
+    /// If there are no receiver slots left, an error will be returned.
+    pub fn dyn_receiver(&self) -> Result<DynReceiver<'_, T>, Error> {
+        self.register_receiver()?;
+        Ok(DynReceiver(Rcv::new(self)))
+    }
+
+    fn register_receiver(&self) -> Result<(), Error> {
+        self.mutex.lock(|state| {
+            let mut s = state.borrow_mut();
+            if s.receiver_count >= N {
+                Err(Error::MaximumReceiversReached)
+            } else {
+                s.receiver_count += 1;
+                Ok(())
+            }
+        })
+    }
+}
+
+/// A sender of a watch.
+pub struct Snd<'a, W: WatchBehavior<T> + ?Sized, T: Clone> {
+    watch: &'a W,
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, W: WatchBehavior<T> + ?Sized, T: Clone> Clone for Snd<'a, W, T> {
+    fn clone(&self) -> Self {
+        Self::new(self.watch)
+    }
+}
+
+impl<'a, W: WatchBehavior<T> + ?Sized, T: Clone> Snd<'a, W, T> {
+    fn new(watch: &'a W) -> Self {
+        Self {
+            watch,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Send a new value, replacing the current one, and wake all receivers.
+    pub fn send(&self, val: T) {
+        self.watch.send(val)
+    }
+
+    /// Modify the current value in place and wake all receivers.
+    ///
+    /// The closure is given `None` if the watch does not contain a value yet.
+    pub fn send_modify(&self, f: impl FnOnce(&mut Option<T>)) {
+        let mut f = Some(f);
+        self.watch.send_modify(&mut |data| (f.take().unwrap())(data))
+    }
+
+    /// Clear the current value, without waking the receivers.
+    pub fn clear(&self) {
+        self.watch.clear()
+    }
+
+    /// Get the current value, if any.
+    pub fn try_get(&self) -> Option<T> {
+        self.watch.try_get(None)
+    }
+
+    /// Check whether the watch contains a value.
+    pub fn contains_value(&self) -> bool {
+        self.watch.contains_value()
+    }
+}
+
+/// A sender that holds a generic reference to the watch
+pub struct Sender<'a, M: RawMutex, T: Clone, const N: usize>(Snd<'a, Watch<M, T, N>, T>);
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> Clone for Sender<'a, M, T, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> Deref for Sender<'a, M, T, N> {
+    type Target = Snd<'a, Watch<M, T, N>, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> DerefMut for Sender<'a, M, T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A sender that holds a dynamic reference to the watch
+pub struct DynSender<'a, T: Clone>(Snd<'a, dyn WatchBehavior<T> + 'a, T>);
+
+impl<'a, T: Clone> Clone for DynSender<'a, T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<'a, T: Clone> Deref for DynSender<'a, T> {
+    type Target = Snd<'a, dyn WatchBehavior<T> + 'a, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T: Clone> DerefMut for DynSender<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A receiver of a watch.
+pub struct Rcv<'a, W: WatchBehavior<T> + ?Sized, T: Clone> {
+    watch: &'a W,
+    /// The id of the last value this receiver has seen
+    at_id: u64,
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, W: WatchBehavior<T> + ?Sized, T: Clone> Rcv<'a, W, T> {
+    fn new(watch: &'a W) -> Self {
+        Self {
+            watch,
+            at_id: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Get the current value, waiting until the watch contains one.
+    ///
+    /// The value is marked as seen by this receiver.
+    pub async fn get(&mut self) -> T {
+        poll_fn(|cx| self.watch.poll_get(&mut self.at_id, cx)).await
+    }
+
+    /// Get the current value, if any, marking it as seen by this receiver.
+    pub fn try_get(&mut self) -> Option<T> {
+        self.watch.try_get(Some(&mut self.at_id))
+    }
+
+    /// Wait for a value this receiver hasn't seen yet.
+    pub async fn changed(&mut self) -> T {
+        poll_fn(|cx| self.watch.poll_changed(&mut self.at_id, cx)).await
+    }
+
+    /// Get the current value if this receiver hasn't seen it yet.
+    pub fn try_changed(&mut self) -> Option<T> {
+        self.watch.try_changed(&mut self.at_id)
+    }
+
+    /// Check whether the watch contains a value.
+    pub fn contains_value(&self) -> bool {
+        self.watch.contains_value()
+    }
+}
+
+impl<'a, W: WatchBehavior<T> + ?Sized, T: Clone> Drop for Rcv<'a, W, T> {
+    fn drop(&mut self) {
+        self.watch.drop_receiver()
+    }
+}
+
+/// A receiver that holds a generic reference to the watch
+pub struct Receiver<'a, M: RawMutex, T: Clone, const N: usize>(Rcv<'a, Watch<M, T, N>, T>);
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> Deref for Receiver<'a, M, T, N> {
+    type Target = Rcv<'a, Watch<M, T, N>, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, M: RawMutex, T: Clone, const N: usize> DerefMut for Receiver<'a, M, T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A receiver that holds a dynamic reference to the watch
+pub struct DynReceiver<'a, T: Clone>(Rcv<'a, dyn WatchBehavior<T> + 'a, T>);
+
+impl<'a, T: Clone> Deref for DynReceiver<'a, T> {
+    type Target = Rcv<'a, dyn WatchBehavior<T> + 'a, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T: Clone> DerefMut for DynReceiver<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking_mutex::raw::NoopRawMutex;
+
+    #[futures_test::test]
+    async fn multiple_receivers() {
+        let watch = Watch::<NoopRawMutex, u32, 2>::new();
+
+        let mut rcv0 = watch.receiver().unwrap();
+        let mut rcv1 = watch.dyn_receiver().unwrap();
+        let snd = watch.sender();
+
+        assert_eq!(rcv0.try_changed(), None);
+        assert_eq!(rcv0.try_get(), None);
+
+        snd.send(10);
+        assert_eq!(rcv0.changed().await, 10);
+        assert_eq!(rcv1.changed().await, 10);
+        assert_eq!(rcv0.try_changed(), None);
+        assert_eq!(rcv1.try_changed(), None);
+
+        // Only the latest value is observed
+        snd.send(20);
+        snd.send(30);
+        assert_eq!(rcv0.changed().await, 30);
+        assert_eq!(rcv1.get().await, 30);
+        assert_eq!(rcv1.try_changed(), None);
+    }
+
+    #[futures_test::test]
+    async fn late_receiver_sees_current_value() {
+        let watch = Watch::<NoopRawMutex, u32, 1>::new();
+        let snd = watch.dyn_sender();
+
+        snd.send(10);
+
+        let mut rcv = watch.receiver().unwrap();
+        assert!(rcv.contains_value());
+        assert_eq!(rcv.changed().await, 10);
+    }
+
+    #[test]
+    fn send_modify_and_clear() {
+        let watch = Watch::<NoopRawMutex, u32, 1>::new();
+        let snd = watch.sender();
+        let mut rcv = watch.receiver().unwrap();
+
+        snd.send_modify(|v| *v = Some(v.unwrap_or(0) + 1));
+        assert_eq!(rcv.try_changed(), Some(1));
+
+        snd.send_modify(|v| *v.as_mut().unwrap() += 1);
+        assert_eq!(rcv.try_changed(), Some(2));
+
+        snd.clear();
+        assert!(!snd.contains_value());
+        assert_eq!(rcv.try_get(), None);
+    }
+
+    #[test]
+    fn limited_receivers() {
+        let watch = Watch::<NoopRawMutex, u32, 2>::new();
+
+        let rcv0 = watch.receiver();
+        let rcv1 = watch.dyn_receiver();
+        let rcv2 = watch.receiver();
+
+        assert!(rcv0.is_ok());
+        assert!(rcv1.is_ok());
+        assert_eq!(rcv2.err().unwrap(), Error::MaximumReceiversReached);
+
+        drop(rcv0);
+        assert!(watch.receiver().is_ok());
+    }
+}
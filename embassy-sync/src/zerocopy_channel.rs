@@ -0,0 +1,439 @@
+//! A zero-copy queue for sending values between asynchronous tasks.
+//!
+//! It can be used concurrently by a producer (sender) and a
+//! consumer (receiver), i.e. it is an "SPSC channel".
+//!
+//! Unlike [`Channel`](crate::channel::Channel), values are not moved in and out of the
+//! channel. The channel works on a caller-provided buffer of slots instead: the sender
+//! borrows a free slot, fills it in place and marks it as sent, then the receiver borrows
+//! the filled slot, processes it in place and marks it as received, which makes the slot
+//! available to the sender again. This avoids copying large values such as network
+//! packets, DMA buffers or audio frames.
+//!
+//! This queue takes a Mutex type so that various
+//! targets can be attained. For example, a ThreadModeMutex can be used
+//! for single-core Cortex-M targets where messages are only passed
+//! between tasks running in thread mode. Similarly, a CriticalSectionMutex
+//! can also be used for single-core targets where messages are to be
+//! passed from exception mode e.g. out of an interrupt handler.
+//!
+//! ```
+//! use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+//! use embassy_sync::zerocopy_channel::Channel;
+//!
+//! let mut buf = [[0u8; 64]; 4];
+//! let mut channel = Channel::<NoopRawMutex, _>::new(&mut buf);
+//! let (mut sender, mut receiver) = channel.split();
+//!
+//! // Fill a slot in place...
+//! let slot = sender.try_send().unwrap();
+//! slot[0] = 42;
+//! sender.send_done();
+//!
+//! // ...and process it in place.
+//! let slot = receiver.try_recv().unwrap();
+//! assert_eq!(slot[0], 42);
+//! receiver.recv_done();
+//! ```
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::{Context, Poll};
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::WakerRegistration;
+
+/// A bounded zero-copy channel for communicating between asynchronous tasks
+/// with backpressure.
+///
+/// The channel holds as many values as the buffer it is created with has slots. Once all
+/// slots are filled, the sender has to wait until the receiver is done with a slot.
+///
+/// All data sent will become available in the same order as it was sent.
+///
+/// The slots are reused, so they keep whatever was written to them before. Values are
+/// never dropped by the channel.
+pub struct Channel<'a, M: RawMutex, T> {
+    buf: *mut T,
+    phantom: PhantomData<&'a mut T>,
+    state: Mutex<M, RefCell<State>>,
+}
+
+unsafe impl<'a, M: RawMutex + Send, T: Send> Send for Channel<'a, M, T> {}
+unsafe impl<'a, M: RawMutex + Sync, T: Send> Sync for Channel<'a, M, T> {}
+
+impl<'a, M: RawMutex, T> Channel<'a, M, T> {
+    /// Create a new channel using `buf` as the storage for its slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty.
+    pub fn new(buf: &'a mut [T]) -> Self {
+        let len = buf.len();
+        assert!(len != 0);
+
+        Self {
+            buf: buf.as_mut_ptr(),
+            phantom: PhantomData,
+            state: Mutex::new(RefCell::new(State {
+                len,
+                front: 0,
+                back: 0,
+                full: false,
+                send_waker: WakerRegistration::new(),
+                recv_waker: WakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Split the channel into its sender and receiver.
+    pub fn split(&mut self) -> (Sender<'_, M, T>, Receiver<'_, M, T>) {
+        (Sender { channel: self }, Receiver { channel: self })
+    }
+
+    /// Remove all values from the channel.
+    pub fn clear(&mut self) {
+        self.state.lock(|s| s.borrow_mut().clear())
+    }
+
+    /// Returns the number of values in the channel.
+    pub fn len(&self) -> usize {
+        self.state.lock(|s| s.borrow().len())
+    }
+
+    /// Returns whether the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.state.lock(|s| s.borrow().is_empty())
+    }
+
+    /// Returns whether the channel is full.
+    pub fn is_full(&self) -> bool {
+        self.state.lock(|s| s.borrow().is_full())
+    }
+}
+
+/// Send-only access to a [`Channel`].
+pub struct Sender<'a, M: RawMutex, T> {
+    channel: &'a Channel<'a, M, T>,
+}
+
+impl<'a, M: RawMutex, T> Sender<'a, M, T> {
+    /// Create a sender borrowing this one, e.g. to pass it to a function taking a sender by value.
+    pub fn borrow(&mut self) -> Sender<'_, M, T> {
+        Sender { channel: self.channel }
+    }
+
+    /// Attempt to immediately borrow a free slot.
+    ///
+    /// Returns `None` if the channel is full. The slot is sent by calling [`send_done`](Sender::send_done).
+    pub fn try_send(&mut self) -> Option<&mut T> {
+        self.channel.state.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            match s.push_index() {
+                Some(i) => Some(unsafe { &mut *self.channel.buf.add(i) }),
+                None => None,
+            }
+        })
+    }
+
+    /// Poll to borrow a free slot.
+    ///
+    /// See [`send`](Sender::send)
+    pub fn poll_send(&mut self, cx: &mut Context) -> Poll<&mut T> {
+        self.channel.state.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            match s.push_index() {
+                Some(i) => Poll::Ready(unsafe { &mut *self.channel.buf.add(i) }),
+                None => {
+                    s.recv_waker.register(cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    /// Borrow a free slot, waiting until one is available.
+    ///
+    /// The slot is sent by calling [`send_done`](Sender::send_done). Until then, repeated calls
+    /// return the same slot.
+    pub async fn send(&mut self) -> &mut T {
+        let i = poll_fn(|cx| {
+            self.channel.state.lock(|s| {
+                let s = &mut *s.borrow_mut();
+                match s.push_index() {
+                    Some(i) => Poll::Ready(i),
+                    None => {
+                        s.recv_waker.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await;
+        unsafe { &mut *self.channel.buf.add(i) }
+    }
+
+    /// Send the slot borrowed with [`send`](Sender::send) or [`try_send`](Sender::try_send) to the receiver.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is full, i.e. no slot was borrowed.
+    pub fn send_done(&mut self) {
+        self.channel.state.lock(|s| s.borrow_mut().push_done())
+    }
+}
+
+/// Receive-only access to a [`Channel`].
+pub struct Receiver<'a, M: RawMutex, T> {
+    channel: &'a Channel<'a, M, T>,
+}
+
+impl<'a, M: RawMutex, T> Receiver<'a, M, T> {
+    /// Create a receiver borrowing this one, e.g. to pass it to a function taking a receiver by value.
+    pub fn borrow(&mut self) -> Receiver<'_, M, T> {
+        Receiver { channel: self.channel }
+    }
+
+    /// Attempt to immediately borrow the next filled slot.
+    ///
+    /// Returns `None` if the channel is empty. The slot is released by calling [`recv_done`](Receiver::recv_done).
+    pub fn try_recv(&mut self) -> Option<&mut T> {
+        self.channel.state.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            match s.pop_index() {
+                Some(i) => Some(unsafe { &mut *self.channel.buf.add(i) }),
+                None => None,
+            }
+        })
+    }
+
+    /// Poll to borrow the next filled slot.
+    ///
+    /// See [`recv`](Receiver::recv)
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<&mut T> {
+        self.channel.state.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            match s.pop_index() {
+                Some(i) => Poll::Ready(unsafe { &mut *self.channel.buf.add(i) }),
+                None => {
+                    s.send_waker.register(cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    /// Borrow the next filled slot, waiting until one is available.
+    ///
+    /// The slot is released by calling [`recv_done`](Receiver::recv_done). Until then, repeated
+    /// calls return the same slot.
+    pub async fn recv(&mut self) -> &mut T {
+        let i = poll_fn(|cx| {
+            self.channel.state.lock(|s| {
+                let s = &mut *s.borrow_mut();
+                match s.pop_index() {
+                    Some(i) => Poll::Ready(i),
+                    None => {
+                        s.send_waker.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await;
+        unsafe { &mut *self.channel.buf.add(i) }
+    }
+
+    /// Release the slot borrowed with [`recv`](Receiver::recv) or [`try_recv`](Receiver::try_recv),
+    /// making it available to the sender again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is empty, i.e. no slot was borrowed.
+    pub fn recv_done(&mut self) {
+        self.channel.state.lock(|s| s.borrow_mut().pop_done())
+    }
+}
+
+struct State {
+    len: usize,
+
+    /// Front index. Always 0..=(N-1)
+    front: usize,
+    /// Back index. Always 0..=(N-1).
+    back: usize,
+
+    /// Used to distinguish "empty" and "full" cases when `front == back`.
+    /// May only be `true` if `front == back`, always `false` otherwise.
+    full: bool,
+
+    /// Woken when a value is sent, i.e. registered by the receiver.
+    send_waker: WakerRegistration,
+    /// Woken when a value is received, i.e. registered by the sender.
+    recv_waker: WakerRegistration,
+}
+
+impl State {
+    fn increment(&self, i: usize) -> usize {
+        if i + 1 == self.len {
+            0
+        } else {
+            i + 1
+        }
+    }
+
+    fn clear(&mut self) {
+        self.front = 0;
+        self.back = 0;
+        self.full = false;
+        self.recv_waker.wake();
+    }
+
+    fn len(&self) -> usize {
+        if self.full {
+            self.len
+        } else if self.back >= self.front {
+            self.back - self.front
+        } else {
+            self.len + self.back - self.front
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.full
+    }
+
+    fn is_empty(&self) -> bool {
+        self.front == self.back && !self.full
+    }
+
+    fn push_index(&mut self) -> Option<usize> {
+        match self.is_full() {
+            true => None,
+            false => Some(self.back),
+        }
+    }
+
+    fn push_done(&mut self) {
+        assert!(!self.is_full());
+        self.back = self.increment(self.back);
+        if self.back == self.front {
+            self.full = true;
+        }
+        self.send_waker.wake();
+    }
+
+    fn pop_index(&mut self) -> Option<usize> {
+        match self.is_empty() {
+            true => None,
+            false => Some(self.front),
+        }
+    }
+
+    fn pop_done(&mut self) {
+        assert!(!self.is_empty());
+        self.front = self.increment(self.front);
+        self.full = false;
+        self.recv_waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use futures_executor::ThreadPool;
+    use futures_timer::Delay;
+    use futures_util::task::SpawnExt;
+    use static_cell::StaticCell;
+
+    use super::*;
+    use crate::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
+
+    #[test]
+    fn send_and_receive() {
+        let mut buf = [0u32; 2];
+        let mut c = Channel::<NoopRawMutex, _>::new(&mut buf);
+        let (mut s, mut r) = c.split();
+        assert!(r.try_recv().is_none());
+
+        *s.try_send().unwrap() = 1;
+        s.send_done();
+        *s.try_send().unwrap() = 2;
+        s.send_done();
+        assert!(s.try_send().is_none());
+
+        assert_eq!(*r.try_recv().unwrap(), 1);
+        r.recv_done();
+        // The released slot is reused.
+        assert_eq!(*s.try_send().unwrap(), 1);
+
+        assert_eq!(*r.try_recv().unwrap(), 2);
+        r.recv_done();
+        assert!(r.try_recv().is_none());
+    }
+
+    #[test]
+    fn len_and_clear() {
+        let mut buf = [0u32; 3];
+        let mut c = Channel::<NoopRawMutex, _>::new(&mut buf);
+        assert!(c.is_empty());
+
+        {
+            let (mut s, mut r) = c.split();
+            for _ in 0..3 {
+                s.try_send().unwrap();
+                s.send_done();
+            }
+            r.try_recv().unwrap();
+            r.recv_done();
+            s.try_send().unwrap();
+            s.send_done();
+        }
+        assert!(c.is_full());
+        assert_eq!(c.len(), 3);
+
+        c.clear();
+        assert!(c.is_empty());
+        assert_eq!(c.len(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn recv_done_when_empty() {
+        let mut buf = [0u32; 1];
+        let mut c = Channel::<NoopRawMutex, _>::new(&mut buf);
+        let (_, mut r) = c.split();
+        r.recv_done();
+    }
+
+    #[futures_test::test]
+    async fn send_waits_for_free_slot() {
+        let executor = ThreadPool::new().unwrap();
+
+        static BUF: StaticCell<[u32; 1]> = StaticCell::new();
+        static CHANNEL: StaticCell<Channel<'static, CriticalSectionRawMutex, u32>> = StaticCell::new();
+        let c = CHANNEL.init(Channel::new(BUF.init([0; 1])));
+        let (mut s, mut r) = c.split();
+
+        *s.send().await = 1;
+        s.send_done();
+
+        let send_task = executor
+            .spawn_with_handle(async move {
+                *s.send().await = 2;
+                s.send_done();
+            })
+            .unwrap();
+
+        Delay::new(Duration::from_millis(100)).await;
+        assert_eq!(*r.recv().await, 1);
+        r.recv_done();
+
+        send_task.await;
+        assert_eq!(*r.recv().await, 2);
+        r.recv_done();
+    }
+}
@@ -0,0 +1,501 @@
+//! Calendar dates and wall-clock time.
+//!
+//! [`Instant`] only counts time since boot. This module adds [`SystemTime`], the time since the
+//! Unix epoch, which is tracked as an offset from [`Instant`] once it has been set from an external
+//! source like SNTP, GPS or a real time clock, and [`DateTime`], its representation as a civil date
+//! and time in UTC.
+//!
+//! Real time clock peripherals can implement the [`Rtc`] trait, so they can be used to set
+//! the system time in a hardware-independent way.
+
+use core::cell::Cell;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+use critical_section::Mutex as CsMutex;
+
+use crate::{Duration, Instant};
+
+/// Errors regarding [`DateTime`] values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The year is not in `1970..=9999`.
+    InvalidYear,
+    /// The month is not in `1..=12`.
+    InvalidMonth,
+    /// The day is not in the month.
+    InvalidDay,
+    /// The hour is not in `0..=23`.
+    InvalidHour,
+    /// The minute is not in `0..=59`.
+    InvalidMinute,
+    /// The second is not in `0..=59`.
+    InvalidSecond,
+}
+
+/// Error returned by [`SystemTime::set_from_rtc`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcError<E> {
+    /// The real time clock returned an error.
+    Rtc(E),
+    /// The real time clock returned an invalid date and time.
+    InvalidDateTime(Error),
+}
+
+/// A day of the week.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum DayOfWeek {
+    Sunday = 0,
+    Monday = 1,
+    Tuesday = 2,
+    Wednesday = 3,
+    Thursday = 4,
+    Friday = 5,
+    Saturday = 6,
+}
+
+/// A civil date and time in UTC, with a resolution of one second.
+///
+/// Years from 1970 up to 9999 are supported, so every `DateTime` maps to a Unix timestamp.
+/// Leap seconds are not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    /// 1970..=9999
+    pub year: u16,
+    /// 1..=12, 1 is January
+    pub month: u8,
+    /// 1..=28,29,30,31 depending on the month
+    pub day: u8,
+    /// 0..=23
+    pub hour: u8,
+    /// 0..=59
+    pub minute: u8,
+    /// 0..=59
+    pub second: u8,
+}
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+/// The Unix timestamp of 9999-12-31 23:59:59
+const MAX_TIMESTAMP: u64 = 253_402_300_799;
+
+impl DateTime {
+    /// Create a new `DateTime`, checking that it is valid.
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Self, Error> {
+        let datetime = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        match datetime.validate() {
+            Ok(()) => Ok(datetime),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check that all fields are within their range.
+    pub const fn validate(&self) -> Result<(), Error> {
+        if self.year < 1970 || self.year > 9999 {
+            Err(Error::InvalidYear)
+        } else if self.month < 1 || self.month > 12 {
+            Err(Error::InvalidMonth)
+        } else if self.day < 1 || self.day > days_in_month(self.year, self.month) {
+            Err(Error::InvalidDay)
+        } else if self.hour > 23 {
+            Err(Error::InvalidHour)
+        } else if self.minute > 59 {
+            Err(Error::InvalidMinute)
+        } else if self.second > 59 {
+            Err(Error::InvalidSecond)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Create a `DateTime` from the number of seconds since the Unix epoch.
+    ///
+    /// Returns [`Error::InvalidYear`] if the timestamp is after the year 9999.
+    pub const fn from_unix_timestamp(secs: u64) -> Result<Self, Error> {
+        if secs > MAX_TIMESTAMP {
+            return Err(Error::InvalidYear);
+        }
+
+        let (year, month, day) = civil_from_days(secs / SECS_PER_DAY);
+        let secs_of_day = secs % SECS_PER_DAY;
+        Ok(Self {
+            year,
+            month,
+            day,
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day / 60 % 60) as u8,
+            second: (secs_of_day % 60) as u8,
+        })
+    }
+
+    /// The number of seconds since the Unix epoch.
+    ///
+    /// The result is only meaningful if the `DateTime` is [valid](DateTime::validate). Use
+    /// [`SystemTime::from_datetime`] to convert a `DateTime` that might not be valid.
+    pub const fn unix_timestamp(&self) -> u64 {
+        days_from_civil(self.year, self.month, self.day)
+            .wrapping_mul(SECS_PER_DAY)
+            .wrapping_add(self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64)
+    }
+
+    /// The day of the week.
+    pub const fn day_of_week(&self) -> DayOfWeek {
+        // The Unix epoch was a Thursday.
+        match days_from_civil(self.year, self.month, self.day).wrapping_add(4) % 7 {
+            0 => DayOfWeek::Sunday,
+            1 => DayOfWeek::Monday,
+            2 => DayOfWeek::Tuesday,
+            3 => DayOfWeek::Wednesday,
+            4 => DayOfWeek::Thursday,
+            5 => DayOfWeek::Friday,
+            _ => DayOfWeek::Saturday,
+        }
+    }
+}
+
+const fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The conversions between days since the epoch and civil dates are based on
+// http://howardhinnant.github.io/date_algorithms.html, restricted to dates after the epoch.
+// Years start on the 1st of March, so the leap day is the last day of the year.
+
+/// Days since the Unix epoch of the given date.
+const fn days_from_civil(year: u16, month: u8, day: u8) -> u64 {
+    // Invalid dates, like the year 0 or the day 0, give meaningless results. The arithmetic wraps
+    // around instead of overflowing for them.
+    let year = (year as u64).wrapping_sub((month <= 2) as u64);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month = month as u64;
+    let day_of_year = ((153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5)
+        .wrapping_add(day as u64)
+        .wrapping_sub(1);
+    let day_of_era = (year_of_era * 365 + year_of_era / 4 - year_of_era / 100).wrapping_add(day_of_year);
+    // 719468 is the number of days from 0000-03-01 to 1970-01-01
+    era.wrapping_mul(146097).wrapping_add(day_of_era).wrapping_sub(719468)
+}
+
+/// The date of the given number of days since the Unix epoch.
+const fn civil_from_days(days: u64) -> (u16, u8, u8) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year as u16, month as u8, day as u8)
+}
+
+/// A point in wall-clock time, as the time since the Unix epoch with a resolution of one microsecond.
+///
+/// Unlike [`Instant`], the system time is not known at boot. It has to be set with
+/// [`SystemTime::set`], after which [`SystemTime::now`] follows [`Instant::now`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SystemTime {
+    micros: u64,
+}
+
+/// The system time at the moment it was set, and the instant it was set at.
+static REFERENCE: CsMutex<Cell<Option<(SystemTime, Instant)>>> = CsMutex::new(Cell::new(None));
+
+impl SystemTime {
+    /// The Unix epoch, 1970-01-01 00:00:00 UTC.
+    pub const UNIX_EPOCH: SystemTime = SystemTime { micros: 0 };
+
+    /// Create a `SystemTime` from the number of seconds since the Unix epoch.
+    pub const fn from_unix_secs(secs: u64) -> Self {
+        Self {
+            micros: secs * 1_000_000,
+        }
+    }
+
+    /// Create a `SystemTime` from the number of microseconds since the Unix epoch.
+    pub const fn from_unix_micros(micros: u64) -> Self {
+        Self { micros }
+    }
+
+    /// The number of whole seconds since the Unix epoch.
+    pub const fn as_unix_secs(&self) -> u64 {
+        self.micros / 1_000_000
+    }
+
+    /// The number of microseconds since the Unix epoch.
+    pub const fn as_unix_micros(&self) -> u64 {
+        self.micros
+    }
+
+    /// Create a `SystemTime` from a [`DateTime`].
+    ///
+    /// Returns an error if the `DateTime` is not [valid](DateTime::validate).
+    pub const fn from_datetime(datetime: &DateTime) -> Result<Self, Error> {
+        match datetime.validate() {
+            Ok(()) => Ok(Self::from_unix_secs(datetime.unix_timestamp())),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Convert to a [`DateTime`], truncating to whole seconds.
+    ///
+    /// Returns [`Error::InvalidYear`] if the time is after the year 9999.
+    pub const fn to_datetime(&self) -> Result<DateTime, Error> {
+        DateTime::from_unix_timestamp(self.as_unix_secs())
+    }
+
+    /// Set the current system time. From now on, [`SystemTime::now`] follows [`Instant::now`]
+    /// starting from `now`.
+    ///
+    /// This can be called again at any time, for example when a new time is received over SNTP.
+    pub fn set(now: SystemTime) {
+        let instant = Instant::now();
+        critical_section::with(|cs| REFERENCE.borrow(cs).set(Some((now, instant))));
+    }
+
+    /// Set the current system time from a real time clock.
+    ///
+    /// The system time is left unchanged if the real time clock returns an error or an invalid
+    /// date and time.
+    pub fn set_from_rtc<R: Rtc + ?Sized>(rtc: &mut R) -> Result<(), RtcError<R::Error>> {
+        let datetime = rtc.datetime().map_err(RtcError::Rtc)?;
+        let now = Self::from_datetime(&datetime).map_err(RtcError::InvalidDateTime)?;
+        Self::set(now);
+        Ok(())
+    }
+
+    /// Returns whether the system time has been set.
+    pub fn is_set() -> bool {
+        critical_section::with(|cs| REFERENCE.borrow(cs).get().is_some())
+    }
+
+    /// The current system time, or `None` if it has not been set yet.
+    pub fn now() -> Option<SystemTime> {
+        Self::at(Instant::now())
+    }
+
+    /// The system time at the given [`Instant`], or `None` if the system time has not been set yet.
+    ///
+    /// This is useful to timestamp events that were recorded as an `Instant`. Instants from
+    /// before the system time was set are converted as well, which might be before the Unix epoch,
+    /// in which case the epoch is returned.
+    pub fn at(instant: Instant) -> Option<SystemTime> {
+        let (reference, reference_instant) = critical_section::with(|cs| REFERENCE.borrow(cs).get())?;
+        Some(match instant.checked_duration_since(reference_instant) {
+            Some(elapsed) => reference + elapsed,
+            None => Self::from_unix_micros(
+                reference
+                    .micros
+                    .saturating_sub((reference_instant - instant).as_micros()),
+            ),
+        })
+    }
+
+    /// The time elapsed since the `earlier` time, or `None` if `earlier` is later than `self`.
+    pub fn checked_duration_since(&self, earlier: SystemTime) -> Option<Duration> {
+        Some(Duration::from_micros(self.micros.checked_sub(earlier.micros)?))
+    }
+
+    /// The time elapsed since the `earlier` time, or zero if `earlier` is later than `self`.
+    pub fn saturating_duration_since(&self, earlier: SystemTime) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or(Duration::from_ticks(0))
+    }
+}
+
+impl Add<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn add(self, other: Duration) -> SystemTime {
+        Self::from_unix_micros(self.micros + other.as_micros())
+    }
+}
+
+impl AddAssign<Duration> for SystemTime {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+impl Sub<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn sub(self, other: Duration) -> SystemTime {
+        Self::from_unix_micros(self.micros - other.as_micros())
+    }
+}
+
+impl SubAssign<Duration> for SystemTime {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl TryFrom<DateTime> for SystemTime {
+    type Error = Error;
+
+    fn try_from(datetime: DateTime) -> Result<Self, Error> {
+        Self::from_datetime(&datetime)
+    }
+}
+
+/// A real time clock, keeping track of the date and time, usually also while the system is
+/// powered down.
+///
+/// HALs implement this for their RTC peripherals, so that applications and libraries can use them
+/// without depending on a specific chip. Use [`SystemTime::set_from_rtc`] to set the system time
+/// from a real time clock.
+pub trait Rtc {
+    /// Error type of the real time clock.
+    type Error;
+
+    /// Get the current date and time.
+    fn datetime(&mut self) -> Result<DateTime, Self::Error>;
+
+    /// Set the current date and time.
+    fn set_datetime(&mut self, datetime: DateTime) -> Result<(), Self::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_timestamp() {
+        let epoch = DateTime::new(1970, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(epoch.unix_timestamp(), 0);
+        assert_eq!(DateTime::from_unix_timestamp(0), Ok(epoch));
+        assert_eq!(epoch.day_of_week(), DayOfWeek::Thursday);
+
+        let leap_day = DateTime::new(2024, 2, 29, 13, 37, 42).unwrap();
+        assert_eq!(leap_day.unix_timestamp(), 1_709_213_862);
+        assert_eq!(DateTime::from_unix_timestamp(1_709_213_862), Ok(leap_day));
+        assert_eq!(leap_day.day_of_week(), DayOfWeek::Thursday);
+
+        let last = DateTime::new(9999, 12, 31, 23, 59, 59).unwrap();
+        assert_eq!(last.unix_timestamp(), MAX_TIMESTAMP);
+        assert_eq!(
+            DateTime::from_unix_timestamp(MAX_TIMESTAMP + 1),
+            Err(Error::InvalidYear)
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        // Every day over a couple of centuries, including the 2000 and 2100 leap year rules
+        for days in 0..(250 * 366) {
+            let secs = days * SECS_PER_DAY + 12345;
+            let datetime = DateTime::from_unix_timestamp(secs).unwrap();
+            assert_eq!(datetime.validate(), Ok(()));
+            assert_eq!(datetime.unix_timestamp(), secs);
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(DateTime::new(1969, 12, 31, 0, 0, 0), Err(Error::InvalidYear));
+        assert_eq!(DateTime::new(2023, 13, 1, 0, 0, 0), Err(Error::InvalidMonth));
+        assert_eq!(DateTime::new(2023, 2, 29, 0, 0, 0), Err(Error::InvalidDay));
+        assert_eq!(DateTime::new(2100, 2, 29, 0, 0, 0), Err(Error::InvalidDay));
+        assert_eq!(DateTime::new(2000, 2, 29, 0, 0, 0).map(|_| ()), Ok(()));
+        assert_eq!(DateTime::new(2023, 4, 31, 0, 0, 0), Err(Error::InvalidDay));
+        assert_eq!(DateTime::new(2023, 1, 1, 24, 0, 0), Err(Error::InvalidHour));
+        assert_eq!(DateTime::new(2023, 1, 1, 0, 60, 0), Err(Error::InvalidMinute));
+        assert_eq!(DateTime::new(2023, 1, 1, 0, 0, 60), Err(Error::InvalidSecond));
+    }
+
+    #[test]
+    fn system_time() {
+        let time = SystemTime::from_datetime(&DateTime::new(2023, 3, 14, 15, 9, 26).unwrap()).unwrap();
+        assert_eq!(time.as_unix_secs(), 1_678_806_566);
+        let later = time + Duration::from_millis(1500);
+        assert_eq!(later.checked_duration_since(time), Some(Duration::from_millis(1500)));
+        assert_eq!(time.checked_duration_since(later), None);
+        assert_eq!(later.to_datetime().unwrap().second, 27);
+    }
+
+    #[test]
+    fn invalid_rtc_datetime() {
+        struct BrokenRtc;
+
+        impl Rtc for BrokenRtc {
+            type Error = ();
+
+            fn datetime(&mut self) -> Result<DateTime, ()> {
+                // A cleared RTC register set, which doesn't hold a valid date
+                Ok(DateTime {
+                    year: 0,
+                    month: 0,
+                    day: 0,
+                    hour: 0,
+                    minute: 0,
+                    second: 0,
+                })
+            }
+
+            fn set_datetime(&mut self, _datetime: DateTime) -> Result<(), ()> {
+                Ok(())
+            }
+        }
+
+        assert_eq!(
+            SystemTime::set_from_rtc(&mut BrokenRtc),
+            Err(RtcError::InvalidDateTime(Error::InvalidYear))
+        );
+
+        let invalid = DateTime {
+            year: 2023,
+            month: 0,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        assert_eq!(SystemTime::try_from(invalid), Err(Error::InvalidMonth));
+        assert_eq!(SystemTime::from_datetime(&invalid), Err(Error::InvalidMonth));
+    }
+
+    #[test]
+    fn invalid_datetime_conversions_do_not_overflow() {
+        // Cleared RTC registers, the year 0 in January, and the day 0 in March
+        for (year, month, day) in [(0, 0, 0), (0, 1, 1), (2023, 3, 0), (u16::MAX, 255, 255)] {
+            let invalid = DateTime {
+                year,
+                month,
+                day,
+                hour: 255,
+                minute: 255,
+                second: 255,
+            };
+            // The results are meaningless, but must not panic.
+            let _ = invalid.unix_timestamp();
+            let _ = invalid.day_of_week();
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::{Duration, Instant};
+use super::{Duration, Instant, Timer};
 
 /// Blocks for at least `duration`.
 pub fn block_for(duration: Duration) {
@@ -14,6 +14,52 @@ pub fn block_for(duration: Duration) {
 /// active driver.
 pub struct Delay;
 
+/// Type implementing async delays that are accurate for short durations.
+///
+/// [`Timer`] and the async [`Delay`] sleep in whole ticks, and the task is only polled again some
+/// time after the timer expired, which makes short delays overshoot. `HybridDelay` sleeps with a
+/// [`Timer`] for the bulk of the delay and busy-waits with [`block_for`] for the last part,
+/// shorter than `threshold`. Delays shorter than `threshold` are busy-waited entirely.
+///
+/// This gives accurate delays without blocking the executor for long. A larger threshold makes
+/// delays more accurate when tasks take a long time to be polled, at the cost of more busy-waiting.
+///
+/// Delays are still only accurate to one tick of the active driver. Durations are rounded up to
+/// whole ticks, and the busy-wait ends when the tick counter reaches the deadline. For example with
+/// `tick-hz-1_000`, a 50µs delay is rounded up to one tick, and takes anywhere up to 1ms depending
+/// on how far into the current tick it starts.
+pub struct HybridDelay {
+    threshold: Duration,
+}
+
+impl HybridDelay {
+    /// Create a delay that busy-waits for the last `threshold` of every delay.
+    pub const fn new(threshold: Duration) -> Self {
+        Self { threshold }
+    }
+
+    /// The duration that is busy-waited at the end of every delay.
+    pub const fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Wait for `duration`.
+    pub async fn delay(&mut self, duration: Duration) {
+        let expires_at = Instant::now() + duration;
+        if duration > self.threshold {
+            Timer::at(expires_at - self.threshold).await;
+        }
+        block_for(expires_at.saturating_duration_since(Instant::now()));
+    }
+}
+
+impl Default for HybridDelay {
+    /// Busy-wait for the last tick of every delay.
+    fn default() -> Self {
+        Self::new(Duration::from_ticks(1))
+    }
+}
+
 #[cfg(feature = "unstable-traits")]
 mod eh1 {
     use super::*;
@@ -34,7 +80,6 @@ mod eh1 {
 #[cfg(all(feature = "unstable-traits", feature = "nightly"))]
 mod eha {
     use super::*;
-    use crate::Timer;
 
     impl embedded_hal_async::delay::DelayUs for Delay {
         type Error = core::convert::Infallible;
@@ -47,6 +92,18 @@ mod eha {
             Ok(Timer::after(Duration::from_millis(millis as _)).await)
         }
     }
+
+    impl embedded_hal_async::delay::DelayUs for HybridDelay {
+        type Error = core::convert::Infallible;
+
+        async fn delay_us(&mut self, micros: u32) -> Result<(), Self::Error> {
+            Ok(self.delay(Duration::from_micros(micros as _)).await)
+        }
+
+        async fn delay_ms(&mut self, millis: u32) -> Result<(), Self::Error> {
+            Ok(self.delay(Duration::from_millis(millis as _)).await)
+        }
+    }
 }
 
 mod eh02 {
@@ -90,3 +147,66 @@ mod eh02 {
         }
     }
 }
+
+#[cfg(all(test, feature = "mock-driver", feature = "generic-queue"))]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll};
+    use std::thread;
+
+    use futures_util::task::noop_waker_ref;
+    use serial_test::serial;
+
+    use super::*;
+    use crate::MockDriver;
+
+    fn setup() -> &'static MockDriver {
+        let driver = MockDriver::get();
+        driver.reset();
+        driver
+    }
+
+    /// Move time forward by `duration` from another thread, so a busy-wait can end.
+    fn advance_later(duration: Duration) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            thread::sleep(core::time::Duration::from_millis(10));
+            MockDriver::get().advance(duration);
+        })
+    }
+
+    #[test]
+    #[serial]
+    fn sleeps_then_busy_waits() {
+        let driver = setup();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let mut delay = HybridDelay::new(Duration::from_millis(1));
+        let mut fut = pin!(delay.delay(Duration::from_millis(10)));
+
+        // The timer expires one threshold before the deadline
+        assert_eq!(fut.as_mut().poll(cx), Poll::Pending);
+        driver.advance(Duration::from_millis(8));
+        assert_eq!(fut.as_mut().poll(cx), Poll::Pending);
+        driver.advance(Duration::from_millis(1));
+
+        // The rest is busy-waited
+        let advance = advance_later(Duration::from_millis(1));
+        assert_eq!(fut.as_mut().poll(cx), Poll::Ready(()));
+        assert_eq!(Instant::now(), Instant::from_millis(10));
+        advance.join().unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn busy_waits_short_delays() {
+        setup();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let mut delay = HybridDelay::new(Duration::from_millis(1));
+        let mut fut = pin!(delay.delay(Duration::from_micros(500)));
+
+        let advance = advance_later(Duration::from_micros(500));
+        assert_eq!(fut.as_mut().poll(cx), Poll::Ready(()));
+        assert_eq!(Instant::now(), Instant::from_micros(500));
+        advance.join().unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex as CsMutex;
+
+use crate::driver::{AlarmHandle, Driver};
+use crate::{Duration, Instant};
+
+/// Alarms are never freed, not even by [`MockDriver::reset`], so this leaves room for the alarms
+/// of several executors and timer queues in a test binary.
+const ALARM_COUNT: usize = 16;
+
+/// Callback of an alarm, with its context
+type Callback = (fn(*mut ()), *mut ());
+
+struct AlarmState {
+    timestamp: u64,
+    callback: Option<Callback>,
+}
+
+unsafe impl Send for AlarmState {}
+
+impl AlarmState {
+    const fn new() -> Self {
+        Self {
+            timestamp: u64::MAX,
+            callback: None,
+        }
+    }
+}
+
+struct InnerMockDriver {
+    now: u64,
+    alarm_count: usize,
+    alarms: [AlarmState; ALARM_COUNT],
+}
+
+impl InnerMockDriver {
+    const fn new() -> Self {
+        const ALARM_NEW: AlarmState = AlarmState::new();
+        Self {
+            now: 0,
+            alarm_count: 0,
+            alarms: [ALARM_NEW; ALARM_COUNT],
+        }
+    }
+}
+
+/// A mock time driver, whose time only moves forward when told to.
+///
+/// Enabled with the `mock-driver` feature, this driver replaces the real one, so code using
+/// [`Timer`](crate::Timer), [`Ticker`](crate::Ticker) or [`with_timeout`](crate::with_timeout) can be
+/// tested on the host deterministically and without actually waiting.
+///
+/// Time starts at zero, and is moved forward with [`advance`](MockDriver::advance). Alarms that
+/// expire are fired synchronously from `advance`, in the order of their timestamps, with the time
+/// set to the timestamp of the alarm.
+///
+/// ## Example
+///
+/// ```
+/// use embassy_time::{Duration, Instant, MockDriver};
+///
+/// let driver = MockDriver::get();
+/// driver.reset();
+///
+/// driver.advance(Duration::from_secs(1));
+/// assert_eq!(Instant::now(), Instant::from_secs(1));
+/// ```
+pub struct MockDriver(CsMutex<RefCell<InnerMockDriver>>);
+
+crate::time_driver_impl!(static DRIVER: MockDriver = MockDriver::new());
+
+impl MockDriver {
+    const fn new() -> Self {
+        Self(CsMutex::new(RefCell::new(InnerMockDriver::new())))
+    }
+
+    /// Get a reference to the global mock driver.
+    pub fn get() -> &'static MockDriver {
+        &DRIVER
+    }
+
+    /// Reset the time to zero and cancel all pending alarms.
+    ///
+    /// Tests sharing the driver should call this first. Allocated alarms and their callbacks are
+    /// kept, because executors and timer queues allocate their alarm only once and keep using it.
+    pub fn reset(&self) {
+        critical_section::with(|cs| {
+            let mut inner = self.0.borrow_ref_mut(cs);
+            inner.now = 0;
+            for alarm in inner.alarms.iter_mut() {
+                alarm.timestamp = u64::MAX;
+            }
+        });
+    }
+
+    /// Move time forward by `duration`, firing the alarms that expire in the meantime.
+    pub fn advance(&self, duration: Duration) {
+        let target = critical_section::with(|cs| self.0.borrow_ref(cs).now) + duration.as_ticks();
+
+        while let Some((callback, ctx)) = self.next_alarm(target) {
+            // The callback might set alarms, so it is called without holding the lock.
+            callback(ctx);
+        }
+
+        critical_section::with(|cs| self.0.borrow_ref_mut(cs).now = target);
+    }
+
+    /// Take the earliest alarm expiring at or before `target`, moving the time to its timestamp.
+    fn next_alarm(&self, target: u64) -> Option<Callback> {
+        critical_section::with(|cs| {
+            let mut inner = self.0.borrow_ref_mut(cs);
+            let alarm = inner
+                .alarms
+                .iter_mut()
+                .filter(|alarm| alarm.timestamp <= target)
+                .min_by_key(|alarm| alarm.timestamp)?;
+
+            let timestamp = alarm.timestamp;
+            alarm.timestamp = u64::MAX;
+            let callback = alarm.callback;
+            inner.now = inner.now.max(timestamp);
+            callback
+        })
+    }
+
+    /// The current time of the driver.
+    pub fn now(&self) -> Instant {
+        Instant::from_ticks(Driver::now(self))
+    }
+}
+
+impl Driver for MockDriver {
+    fn now(&self) -> u64 {
+        critical_section::with(|cs| self.0.borrow_ref(cs).now)
+    }
+
+    unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+        critical_section::with(|cs| {
+            let mut inner = self.0.borrow_ref_mut(cs);
+            if inner.alarm_count < ALARM_COUNT {
+                inner.alarm_count += 1;
+                Some(AlarmHandle::new(inner.alarm_count as u8 - 1))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn set_alarm_callback(&self, alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+        critical_section::with(|cs| {
+            self.0.borrow_ref_mut(cs).alarms[alarm.id() as usize].callback = Some((callback, ctx));
+        })
+    }
+
+    fn set_alarm(&self, alarm: AlarmHandle, timestamp: u64) -> bool {
+        critical_section::with(|cs| {
+            let mut inner = self.0.borrow_ref_mut(cs);
+            if timestamp <= inner.now {
+                false
+            } else {
+                inner.alarms[alarm.id() as usize].timestamp = timestamp;
+                true
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use serial_test::serial;
+
+    use super::*;
+
+    fn setup() -> &'static MockDriver {
+        let driver = MockDriver::get();
+        driver.reset();
+        driver
+    }
+
+    #[test]
+    #[serial]
+    fn advance() {
+        let driver = setup();
+        assert_eq!(Instant::now(), Instant::from_ticks(0));
+
+        driver.advance(Duration::from_secs(1));
+        assert_eq!(Instant::now(), Instant::from_secs(1));
+        assert_eq!(driver.now(), Instant::from_secs(1));
+    }
+
+    #[test]
+    #[serial]
+    fn alarms_fire_in_order() {
+        static FIRED: CsMutex<RefCell<heapless::Vec<(u8, u64), 4>>> = CsMutex::new(RefCell::new(heapless::Vec::new()));
+
+        fn callback(ctx: *mut ()) {
+            let id = ctx as usize as u8;
+            critical_section::with(|cs| FIRED.borrow_ref_mut(cs).push((id, Instant::now().as_ticks())).unwrap());
+        }
+
+        let driver = setup();
+        critical_section::with(|cs| FIRED.borrow_ref_mut(cs).clear());
+
+        let a = unsafe { driver.allocate_alarm() }.unwrap();
+        let b = unsafe { driver.allocate_alarm() }.unwrap();
+        driver.set_alarm_callback(a, callback, a.id() as usize as *mut ());
+        driver.set_alarm_callback(b, callback, b.id() as usize as *mut ());
+
+        assert!(driver.set_alarm(a, 300));
+        assert!(driver.set_alarm(b, 200));
+
+        driver.advance(Duration::from_ticks(250));
+        assert!(!driver.set_alarm(b, 100));
+        driver.advance(Duration::from_ticks(250));
+
+        critical_section::with(|cs| assert_eq!(FIRED.borrow_ref(cs).as_slice(), &[(b.id(), 200), (a.id(), 300)]));
+        assert_eq!(driver.now(), Instant::from_ticks(500));
+    }
+
+    #[test]
+    #[serial]
+    fn callback_can_set_alarm() {
+        static COUNT: CsMutex<Cell<u64>> = CsMutex::new(Cell::new(0));
+
+        // Fires every 100 ticks, like a periodic timer
+        fn callback(ctx: *mut ()) {
+            let alarm = unsafe { AlarmHandle::new(ctx as usize as u8) };
+            critical_section::with(|cs| COUNT.borrow(cs).set(COUNT.borrow(cs).get() + 1));
+            assert!(DRIVER.set_alarm(alarm, Instant::now().as_ticks() + 100));
+        }
+
+        let driver = setup();
+        critical_section::with(|cs| COUNT.borrow(cs).set(0));
+
+        let alarm = unsafe { driver.allocate_alarm() }.unwrap();
+        driver.set_alarm_callback(alarm, callback, alarm.id() as usize as *mut ());
+        assert!(driver.set_alarm(alarm, 100));
+
+        driver.advance(Duration::from_ticks(1050));
+        critical_section::with(|cs| assert_eq!(COUNT.borrow(cs).get(), 10));
+    }
+
+    #[test]
+    #[serial]
+    fn reset_keeps_alarms() {
+        static FIRED: CsMutex<Cell<bool>> = CsMutex::new(Cell::new(false));
+
+        fn callback(_ctx: *mut ()) {
+            critical_section::with(|cs| FIRED.borrow(cs).set(true));
+        }
+
+        let driver = setup();
+        critical_section::with(|cs| FIRED.borrow(cs).set(false));
+
+        let alarm = unsafe { driver.allocate_alarm() }.unwrap();
+        driver.set_alarm_callback(alarm, callback, core::ptr::null_mut());
+        assert!(driver.set_alarm(alarm, 100));
+
+        // The pending alarm is cancelled
+        driver.reset();
+        driver.advance(Duration::from_ticks(200));
+        critical_section::with(|cs| assert!(!FIRED.borrow(cs).get()));
+
+        // The alarm is still allocated, and keeps its callback
+        assert_ne!(unsafe { driver.allocate_alarm() }.unwrap().id(), alarm.id());
+        assert!(driver.set_alarm(alarm, 300));
+        driver.advance(Duration::from_ticks(100));
+        critical_section::with(|cs| assert!(FIRED.borrow(cs).get()));
+    }
+
+    #[cfg(feature = "generic-queue")]
+    #[test]
+    #[serial]
+    fn timer_across_resets() {
+        use core::future::Future;
+        use core::pin::Pin;
+        use core::sync::atomic::{AtomicBool, Ordering};
+        use core::task::{Context, Poll};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        use crate::Timer;
+
+        struct Flag(AtomicBool);
+
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        // The timer queue allocates its alarm on first use, and has to keep working after a reset
+        for _ in 0..2 {
+            let driver = setup();
+            let flag = Arc::new(Flag(AtomicBool::new(false)));
+            let waker = flag.clone().into();
+            let mut cx = Context::from_waker(&waker);
+
+            let mut timer = Timer::after(Duration::from_millis(10));
+            assert_eq!(Pin::new(&mut timer).poll(&mut cx), Poll::Pending);
+
+            driver.advance(Duration::from_millis(9));
+            assert!(!flag.0.load(Ordering::Relaxed));
+            driver.advance(Duration::from_millis(1));
+            assert!(flag.0.load(Ordering::Relaxed));
+            assert_eq!(Pin::new(&mut timer).poll(&mut cx), Poll::Ready(()));
+        }
+    }
+}
@@ -8,6 +8,7 @@
 // This mod MUST go first, so that the others see its macros.
 pub(crate) mod fmt;
 
+pub mod datetime;
 mod delay;
 pub mod driver;
 mod duration;
@@ -16,6 +17,8 @@ pub mod queue;
 mod tick;
 mod timer;
 
+#[cfg(feature = "mock-driver")]
+mod driver_mock;
 #[cfg(feature = "std")]
 mod driver_std;
 #[cfg(feature = "wasm")]
@@ -23,10 +26,13 @@ mod driver_wasm;
 #[cfg(feature = "generic-queue")]
 mod queue_generic;
 
-pub use delay::{block_for, Delay};
+pub use datetime::{DateTime, SystemTime};
+pub use delay::{block_for, Delay, HybridDelay};
+#[cfg(feature = "mock-driver")]
+pub use driver_mock::MockDriver;
 pub use duration::Duration;
 pub use instant::Instant;
-pub use timer::{with_timeout, Ticker, TimeoutError, Timer};
+pub use timer::{with_timeout, MissedTickBehavior, Ticker, TimeoutError, Timer};
 
 /// Ticks per second of the global timebase.
 ///
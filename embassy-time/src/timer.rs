@@ -121,30 +121,116 @@ impl Future for Timer {
 ///     }
 /// }
 /// ```
+///
+/// If the ticker falls behind by more than one period, for example because `foo` blocked for a
+/// long time, the missed ticks are handled according to its [`MissedTickBehavior`].
 pub struct Ticker {
     expires_at: Instant,
     duration: Duration,
+    behavior: MissedTickBehavior,
+    missed_ticks: u64,
+}
+
+/// How a [`Ticker`] handles ticks that were missed because it was not polled in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MissedTickBehavior {
+    /// Fire the missed ticks back to back until the ticker has caught up with its schedule.
+    ///
+    /// This is the default. No ticks are lost, so [`Ticker::missed_ticks`] stays zero.
+    Burst,
+    /// Skip the missed ticks, and fire the next tick at its time in the original schedule.
+    Skip,
+    /// Skip the missed ticks, and fire the next tick one period after the late tick.
+    ///
+    /// This shifts the schedule, so ticks are never closer together than one period.
+    Delay,
 }
 
 impl Ticker {
     /// Creates a new ticker that ticks at the specified duration interval.
     pub fn every(duration: Duration) -> Self {
         let expires_at = Instant::now() + duration;
-        Self { expires_at, duration }
+        Self {
+            expires_at,
+            duration,
+            behavior: MissedTickBehavior::Burst,
+            missed_ticks: 0,
+        }
+    }
+
+    /// Set how missed ticks are handled.
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.behavior = behavior;
+    }
+
+    /// Returns how missed ticks are handled.
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.behavior
+    }
+
+    /// Returns the total amount of ticks that were skipped because the ticker fell behind.
+    pub fn missed_ticks(&self) -> u64 {
+        self.missed_ticks
+    }
+
+    /// Restarts the ticker, so the next tick fires one period from now.
+    pub fn reset(&mut self) {
+        self.reset_at(Instant::now() + self.duration);
+    }
+
+    /// Restarts the ticker, so the next tick fires at the given [`Instant`].
+    ///
+    /// Later ticks follow one period apart.
+    pub fn reset_at(&mut self, deadline: Instant) {
+        self.expires_at = deadline;
     }
 
     /// Waits for the next tick
     pub fn next(&mut self) -> impl Future<Output = ()> + '_ {
-        poll_fn(|cx| {
-            if self.expires_at <= Instant::now() {
-                let dur = self.duration;
-                self.expires_at += dur;
-                Poll::Ready(())
-            } else {
-                schedule_wake(self.expires_at, cx.waker());
-                Poll::Pending
+        poll_fn(|cx| self.poll_tick(cx))
+    }
+
+    fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.try_tick() {
+            Poll::Ready(())
+        } else {
+            schedule_wake(self.expires_at, cx.waker());
+            Poll::Pending
+        }
+    }
+
+    /// Take the current tick if it expired, scheduling the next one.
+    fn try_tick(&mut self) -> bool {
+        let now = Instant::now();
+        if self.expires_at <= now {
+            self.advance(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Schedule the tick after the one that expired, as seen at `now`.
+    fn advance(&mut self, now: Instant) {
+        let period = self.duration.as_ticks();
+        // The amount of ticks that expired as well, besides the current one.
+        let missed = match period {
+            0 => 0,
+            _ => (now - self.expires_at).as_ticks() / period,
+        };
+
+        match self.behavior {
+            MissedTickBehavior::Burst => self.expires_at += self.duration,
+            MissedTickBehavior::Skip => {
+                self.missed_ticks += missed;
+                self.expires_at = Instant::from_ticks(self.expires_at.as_ticks() + (missed + 1) * period);
+            }
+            MissedTickBehavior::Delay => {
+                self.missed_ticks += missed;
+                self.expires_at = now + self.duration;
             }
-        })
+        }
     }
 }
 
@@ -153,14 +239,7 @@ impl Unpin for Ticker {}
 impl Stream for Ticker {
     type Item = ();
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.expires_at <= Instant::now() {
-            let dur = self.duration;
-            self.expires_at += dur;
-            Poll::Ready(Some(()))
-        } else {
-            schedule_wake(self.expires_at, cx.waker());
-            Poll::Pending
-        }
+        self.poll_tick(cx).map(Some)
     }
 }
 
@@ -171,3 +250,127 @@ extern "Rust" {
 fn schedule_wake(at: Instant, waker: &Waker) {
     unsafe { _embassy_time_schedule_wake(at, waker) }
 }
+
+#[cfg(all(test, feature = "mock-driver"))]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+    use crate::MockDriver;
+
+    fn setup() -> &'static MockDriver {
+        let driver = MockDriver::get();
+        driver.reset();
+        driver
+    }
+
+    /// Take all the ticks that expired, returning how many there were.
+    fn ticks(ticker: &mut Ticker) -> u64 {
+        let mut ticks = 0;
+        while ticker.try_tick() {
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    #[serial]
+    fn ticks_every_period() {
+        let driver = setup();
+        let mut ticker = Ticker::every(Duration::from_millis(10));
+
+        driver.advance(Duration::from_millis(9));
+        assert_eq!(ticks(&mut ticker), 0);
+        driver.advance(Duration::from_millis(1));
+        assert_eq!(ticks(&mut ticker), 1);
+        driver.advance(Duration::from_millis(10));
+        assert_eq!(ticks(&mut ticker), 1);
+        assert_eq!(ticker.missed_ticks(), 0);
+    }
+
+    #[test]
+    #[serial]
+    fn burst_fires_missed_ticks() {
+        let driver = setup();
+        let mut ticker = Ticker::every(Duration::from_millis(10));
+        assert_eq!(ticker.missed_tick_behavior(), MissedTickBehavior::Burst);
+
+        driver.advance(Duration::from_millis(35));
+        assert_eq!(ticks(&mut ticker), 3);
+        assert_eq!(ticker.missed_ticks(), 0);
+
+        // The schedule is kept
+        driver.advance(Duration::from_millis(5));
+        assert_eq!(ticks(&mut ticker), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn skip_keeps_schedule() {
+        let driver = setup();
+        let mut ticker = Ticker::every(Duration::from_millis(10));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        driver.advance(Duration::from_millis(35));
+        assert_eq!(ticks(&mut ticker), 1);
+        assert_eq!(ticker.missed_ticks(), 2);
+
+        // The next tick is at 40ms, in the original schedule
+        driver.advance(Duration::from_millis(4));
+        assert_eq!(ticks(&mut ticker), 0);
+        driver.advance(Duration::from_millis(1));
+        assert_eq!(ticks(&mut ticker), 1);
+
+        driver.advance(Duration::from_millis(20));
+        assert_eq!(ticks(&mut ticker), 1);
+        assert_eq!(ticker.missed_ticks(), 3);
+    }
+
+    #[test]
+    #[serial]
+    fn delay_shifts_schedule() {
+        let driver = setup();
+        let mut ticker = Ticker::every(Duration::from_millis(10));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        driver.advance(Duration::from_millis(35));
+        assert_eq!(ticks(&mut ticker), 1);
+        assert_eq!(ticker.missed_ticks(), 2);
+
+        // The next tick is one period after the late one, at 45ms
+        driver.advance(Duration::from_millis(9));
+        assert_eq!(ticks(&mut ticker), 0);
+        driver.advance(Duration::from_millis(1));
+        assert_eq!(ticks(&mut ticker), 1);
+
+        // A tick that is late by less than a period doesn't count as missed
+        driver.advance(Duration::from_millis(15));
+        assert_eq!(ticks(&mut ticker), 1);
+        assert_eq!(ticker.missed_ticks(), 2);
+    }
+
+    #[test]
+    #[serial]
+    fn reset_at_restarts_schedule() {
+        let driver = setup();
+        let mut ticker = Ticker::every(Duration::from_millis(10));
+
+        ticker.reset_at(Instant::from_millis(3));
+        driver.advance(Duration::from_millis(3));
+        assert_eq!(ticks(&mut ticker), 1);
+
+        // Later ticks follow one period apart
+        driver.advance(Duration::from_millis(9));
+        assert_eq!(ticks(&mut ticker), 0);
+        driver.advance(Duration::from_millis(1));
+        assert_eq!(ticks(&mut ticker), 1);
+
+        // `reset` restarts one period from now
+        driver.advance(Duration::from_millis(5));
+        ticker.reset();
+        driver.advance(Duration::from_millis(9));
+        assert_eq!(ticks(&mut ticker), 0);
+        driver.advance(Duration::from_millis(1));
+        assert_eq!(ticks(&mut ticker), 1);
+    }
+}
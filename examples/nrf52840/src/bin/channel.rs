@@ -2,7 +2,7 @@
 #![no_main]
 #![feature(type_alias_impl_trait)]
 
-use defmt::unwrap;
+use defmt::{unwrap, Format};
 use embassy_executor::Spawner;
 use embassy_nrf::gpio::{Level, Output, OutputDrive};
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
@@ -10,6 +10,7 @@ use embassy_sync::channel::Channel;
 use embassy_time::{Duration, Timer};
 use {defmt_rtt as _, panic_probe as _};
 
+#[derive(Format)]
 enum LedState {
     On,
     Off,
@@ -20,9 +21,9 @@ static CHANNEL: Channel<ThreadModeRawMutex, LedState, 1> = Channel::new();
 #[embassy_executor::task]
 async fn my_task() {
     loop {
-        CHANNEL.send(LedState::On).await;
+        unwrap!(CHANNEL.send(LedState::On).await);
         Timer::after(Duration::from_secs(1)).await;
-        CHANNEL.send(LedState::Off).await;
+        unwrap!(CHANNEL.send(LedState::Off).await);
         Timer::after(Duration::from_secs(1)).await;
     }
 }
@@ -35,7 +36,7 @@ async fn main(spawner: Spawner) {
     unwrap!(spawner.spawn(my_task()));
 
     loop {
-        match CHANNEL.recv().await {
+        match unwrap!(CHANNEL.recv().await) {
             LedState::On => led.set_high(),
             LedState::Off => led.set_low(),
         }
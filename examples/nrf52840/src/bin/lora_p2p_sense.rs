@@ -37,7 +37,7 @@ async fn temperature_task(publisher: Publisher<'static, CriticalSectionRawMutex,
     // Publish a fake temperature every 43 seconds, minimizing LORA traffic.
     loop {
         Timer::after(Duration::from_secs(43)).await;
-        publisher.publish(Message::Temperature(9)).await;
+        unwrap!(publisher.publish(Message::Temperature(9)).await);
     }
 }
 
@@ -46,7 +46,7 @@ async fn motion_detection_task(publisher: Publisher<'static, CriticalSectionRawM
     // Publish a fake motion detection every 79 seconds, minimizing LORA traffic.
     loop {
         Timer::after(Duration::from_secs(79)).await;
-        publisher.publish(Message::MotionDetected).await;
+        unwrap!(publisher.publish(Message::MotionDetected).await);
     }
 }
 
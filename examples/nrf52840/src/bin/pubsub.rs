@@ -56,7 +56,7 @@ async fn main(spawner: Spawner) {
         // - The subscribers won't miss any messages any more
         // - Trying to publish now has some wait time when the queue is full
 
-        // message_publisher.publish(message).await;
+        // unwrap!(message_publisher.publish(message).await);
 
         index += 1;
     }
@@ -46,7 +46,7 @@ async fn main(spawner: Spawner) {
     // back out the buffer we receive from the read
     // task.
     loop {
-        let buf = CHANNEL.recv().await;
+        let buf = unwrap!(CHANNEL.recv().await);
         info!("writing...");
         unwrap!(tx.write(&buf).await);
     }
@@ -58,6 +58,6 @@ async fn reader(mut rx: UarteRx<'static, UARTE0>) {
     loop {
         info!("reading...");
         unwrap!(rx.read(&mut buf).await);
-        CHANNEL.send(buf).await;
+        unwrap!(CHANNEL.send(buf).await);
     }
 }
@@ -41,7 +41,7 @@ async fn main(spawner: Spawner) -> ! {
     unwrap!(spawner.spawn(reader(rx)));
 
     loop {
-        let buf = CHANNEL.recv().await;
+        let buf = unwrap!(CHANNEL.recv().await);
         info!("writing...");
         unwrap!(tx.write(&buf).await);
     }
@@ -53,6 +53,6 @@ async fn reader(mut rx: UartRx<'static, UART7, DMA1_CH1>) {
     loop {
         info!("reading...");
         unwrap!(rx.read(&mut buf).await);
-        CHANNEL.send(buf).await;
+        unwrap!(CHANNEL.send(buf).await);
     }
 }